@@ -0,0 +1,244 @@
+//! GDB remote stub debugger mode.
+//!
+//! The SBF loaders are replaced by an entrypoint which executes the program
+//! under test in the interpreter with rbpf's GDB remote stub attached, so it
+//! can be stepped through under the exact fixture inputs. Other programs, and
+//! the loaders' management instructions, are processed as usual.
+//!
+//! Executing a loaded program relies on the same lifetime juggling as the
+//! loader itself.
+
+use {
+    solana_bpf_loader_program::{create_vm, process_instruction_inner, serialization},
+    solana_program_runtime::{
+        invoke_context::InvokeContext,
+        loaded_programs::{LoadedProgram, LoadedProgramType},
+    },
+    solana_rbpf::{
+        declare_builtin_function,
+        elf::Executable,
+        error::{EbpfError, ProgramResult},
+        memory_region::{AccessType, MemoryMapping},
+    },
+    solana_sdk::{
+        bpf_loader_deprecated,
+        entrypoint::{MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+        feature_set::bpf_account_data_direct_mapping,
+        instruction::InstructionError,
+        native_loader,
+        pubkey::Pubkey,
+        transaction_context::IndexOfAccount,
+    },
+    std::{cell::Cell, sync::Arc},
+};
+
+thread_local! {
+    // The program under test and the port its debug server listens on, for
+    // executions on this thread.
+    static DEBUG_TARGET: Cell<Option<(Pubkey, u16)>> = const { Cell::new(None) };
+}
+
+/// Run `f` with every invocation of `program_id` executed under a GDB remote
+/// stub listening on `debug_port`.
+pub fn with_debug_port<T>(program_id: Pubkey, debug_port: u16, f: impl FnOnce() -> T) -> T {
+    let _guard = DebugTargetGuard(
+        DEBUG_TARGET.with(|target| target.replace(Some((program_id, debug_port)))),
+    );
+    f()
+}
+
+// Restores the previous debug target when dropped, including when the
+// instruction panics.
+struct DebugTargetGuard(Option<(Pubkey, u16)>);
+
+impl Drop for DebugTargetGuard {
+    fn drop(&mut self) {
+        DEBUG_TARGET.with(|target| target.set(self.0));
+    }
+}
+
+pub use entrypoint::LoaderEntrypoint;
+
+// The entrypoint of the SBF loaders under a debugger. The struct declared by
+// the macro can't be documented.
+#[allow(missing_docs)]
+mod entrypoint {
+    use super::*;
+
+    declare_builtin_function!(
+        LoaderEntrypoint,
+        fn rust(
+            invoke_context: &mut InvokeContext,
+            _arg0: u64,
+            _arg1: u64,
+            _arg2: u64,
+            _arg3: u64,
+            _arg4: u64,
+            _memory_mapping: &mut MemoryMapping,
+        ) -> Result<u64, Box<dyn std::error::Error>> {
+            match debug_executable(invoke_context)? {
+                Some((executable, debug_port)) => {
+                    execute(&executable, invoke_context, debug_port).map(|_| 0)
+                }
+                None => process_instruction_inner(invoke_context),
+            }
+        }
+    );
+}
+
+// The loaded program to debug, if the current instruction invokes the
+// program under test, and its debug port.
+fn debug_executable(
+    invoke_context: &InvokeContext,
+) -> Result<Option<(Arc<LoadedProgram>, u16)>, InstructionError> {
+    let Some((program_id, debug_port)) = DEBUG_TARGET.with(|target| target.get()) else {
+        return Ok(None);
+    };
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let program_account =
+        instruction_context.try_borrow_last_program_account(transaction_context)?;
+
+    // Loader management instructions, and programs which can't be executed,
+    // are left to the loader.
+    if *program_account.get_key() != program_id
+        || native_loader::check_id(program_account.get_owner())
+        || !program_account.is_executable(&invoke_context.feature_set)
+    {
+        return Ok(None);
+    }
+    Ok(invoke_context
+        .find_program_in_cache(&program_id)
+        .filter(|loaded_program| {
+            matches!(
+                loaded_program.program,
+                LoadedProgramType::LegacyV0(_) | LoadedProgramType::LegacyV1(_)
+            )
+        })
+        .map(|loaded_program| (loaded_program, debug_port)))
+}
+
+// Execute a loaded program as the loader does, but in the interpreter with
+// the debug server attached.
+fn execute<'a, 'b: 'a>(
+    loaded_program: &'a Arc<LoadedProgram>,
+    invoke_context: &'a mut InvokeContext<'b>,
+    debug_port: u16,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (LoadedProgramType::LegacyV0(executable) | LoadedProgramType::LegacyV1(executable)) =
+        &loaded_program.program
+    else {
+        return Err(Box::new(InstructionError::IncorrectProgramId));
+    };
+    // SAFETY: As in the loader, the cache drops the executable's invoke
+    // context lifetime, which is reintroduced for this execution.
+    let executable: &'a Executable<InvokeContext<'b>> =
+        unsafe { std::mem::transmute::<&Executable<InvokeContext<'static>>, _>(executable) };
+
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let is_loader_deprecated = *instruction_context
+        .try_borrow_last_program_account(transaction_context)?
+        .get_owner()
+        == bpf_loader_deprecated::id();
+    let direct_mapping = invoke_context
+        .feature_set
+        .is_active(&bpf_account_data_direct_mapping::id());
+
+    let (parameter_bytes, regions, accounts_metadata) = serialization::serialize_parameters(
+        invoke_context.transaction_context,
+        instruction_context,
+        !direct_mapping,
+        &invoke_context.feature_set,
+    )?;
+
+    // The account data regions, to map access violations to more specific
+    // errors.
+    let account_region_addrs = accounts_metadata
+        .iter()
+        .map(|metadata| {
+            let max_data_increase = if is_loader_deprecated {
+                0
+            } else {
+                MAX_PERMITTED_DATA_INCREASE as u64
+            };
+            metadata.vm_data_addr
+                ..metadata.vm_data_addr + metadata.original_data_len as u64 + max_data_increase
+        })
+        .collect::<Vec<_>>();
+
+    let execution_result = {
+        create_vm!(vm, executable, regions, accounts_metadata, invoke_context);
+        let mut vm = vm.map_err(|_| InstructionError::ProgramEnvironmentSetupFailure)?;
+        vm.debug_port = Some(debug_port);
+        let (_compute_units_consumed, result) = vm.execute_program(executable, true);
+        drop(vm);
+        match result {
+            ProgramResult::Ok(status) if status != SUCCESS => {
+                Err(Box::new(InstructionError::from(status)) as Box<dyn std::error::Error>)
+            }
+            ProgramResult::Err(EbpfError::AccessViolation(AccessType::Store, address, _, _))
+                if direct_mapping
+                    && account_region_addrs
+                        .iter()
+                        .any(|region| region.contains(&address)) =>
+            {
+                let index = account_region_addrs
+                    .iter()
+                    .position(|region| region.contains(&address))
+                    .unwrap();
+                let transaction_context = &invoke_context.transaction_context;
+                let instruction_context = transaction_context.get_current_instruction_context()?;
+                let account = instruction_context
+                    .try_borrow_instruction_account(transaction_context, index as IndexOfAccount)?;
+                Err(
+                    Box::new(if account.is_executable(&invoke_context.feature_set) {
+                        InstructionError::ExecutableDataModified
+                    } else if account.is_writable() {
+                        InstructionError::ExternalAccountDataModified
+                    } else {
+                        InstructionError::ReadonlyDataModified
+                    }) as Box<dyn std::error::Error>,
+                )
+            }
+            ProgramResult::Err(EbpfError::SyscallError(error)) => Err(error),
+            ProgramResult::Err(error) => Err(error.into()),
+            ProgramResult::Ok(_) => Ok(()),
+        }
+    };
+
+    execution_result.and_then(|_| {
+        serialization::deserialize_parameters(
+            invoke_context.transaction_context,
+            invoke_context
+                .transaction_context
+                .get_current_instruction_context()?,
+            !direct_mapping,
+            parameter_bytes.as_slice(),
+            &invoke_context.get_syscall_context()?.accounts_metadata,
+            &invoke_context.feature_set,
+        )
+        .map_err(|error| Box::new(error) as Box<dyn std::error::Error>)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::panic};
+
+    #[test]
+    fn test_with_debug_port_panic() {
+        let program_id = Pubkey::new_unique();
+        let result = panic::catch_unwind(|| {
+            with_debug_port(program_id, 1212, || {
+                assert_eq!(
+                    DEBUG_TARGET.with(|target| target.get()),
+                    Some((program_id, 1212))
+                );
+                panic!("Instruction panicked");
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(DEBUG_TARGET.with(|target| target.get()), None);
+    }
+}
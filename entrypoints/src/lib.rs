@@ -0,0 +1,13 @@
+//! Program entrypoints which stand in for the SBF loaders in protosol.
+//!
+//! Natively executing a program, and executing one under a debugger, rely on
+//! the same raw pointer and lifetime juggling as `solana-program-test` and
+//! the loaders themselves. That unsafe code is kept in this crate, so that
+//! protosol itself can forbid it.
+
+#![deny(missing_docs)]
+
+#[cfg(feature = "debugger")]
+pub mod debugger;
+#[cfg(feature = "native")]
+pub mod native;
@@ -0,0 +1,466 @@
+//! Native Rust processor mode.
+//!
+//! A program's `process_instruction` can be executed natively, rather than
+//! from an SBF ELF, through the same `InvokeContext`, sysvars and account
+//! handling. The program is registered as a builtin, and its syscalls are
+//! routed back into the invoke context by stubs.
+//!
+//! Native execution relies on the same raw pointer juggling as
+//! `solana-program-test`.
+
+use {
+    solana_bpf_loader_program::serialization::serialize_parameters,
+    solana_program_runtime::{
+        declare_process_instruction, ic_msg, invoke_context::InvokeContext,
+        solana_rbpf::vm::ContextObject, stable_log, timings::ExecuteTimings,
+    },
+    solana_sdk::{
+        account_info::AccountInfo,
+        entrypoint::{deserialize, ProcessInstruction, SUCCESS},
+        instruction::{Instruction, InstructionError},
+        program_error::{ProgramError, UNSUPPORTED_SYSVAR},
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+        pubkey::Pubkey,
+        stable_layout::stable_instruction::StableInstruction,
+        sysvar::Sysvar,
+        transaction_context::IndexOfAccount,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        collections::{HashMap, HashSet},
+        mem::size_of,
+        sync::{Arc, Once},
+    },
+};
+
+thread_local! {
+    // The invoke context of the instruction being natively executed on this
+    // thread, for the syscall stubs.
+    static INVOKE_CONTEXT: Cell<*mut ()> = const { Cell::new(std::ptr::null_mut()) };
+    // The native processor of the program under test on this thread.
+    static PROCESSOR: Cell<Option<ProcessInstruction>> = const { Cell::new(None) };
+    // The serialized parameters buffers of the native instructions being
+    // executed on this thread, innermost last, which the account infos
+    // point into.
+    static PARAMETERS: RefCell<Vec<(*mut u8, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn set_invoke_context(new: &mut InvokeContext) {
+    let ptr = (new as *mut InvokeContext).cast::<()>();
+    INVOKE_CONTEXT.with(|invoke_context| invoke_context.set(ptr));
+}
+
+fn get_invoke_context<'a, 'b>() -> &'a mut InvokeContext<'b> {
+    let ptr = INVOKE_CONTEXT.with(|invoke_context| invoke_context.get());
+    if ptr.is_null() {
+        panic!("Invoke context not set!");
+    }
+    // SAFETY: The pointer is only set while the native processor runs within
+    // the invoke context, which outlives the syscall stubs' use of it.
+    unsafe { &mut *ptr.cast::<InvokeContext<'b>>() }
+}
+
+/// Run `f` with `process_instruction` registered as the native processor of
+/// the program under test.
+pub fn with_processor<T>(process_instruction: ProcessInstruction, f: impl FnOnce() -> T) -> T {
+    static INSTALL_SYSCALL_STUBS: Once = Once::new();
+    INSTALL_SYSCALL_STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(NativeSyscallStubs));
+    });
+
+    let _guard = ProcessorGuard {
+        processor: PROCESSOR.with(|processor| processor.replace(Some(process_instruction))),
+        invoke_context: INVOKE_CONTEXT.with(|invoke_context| invoke_context.get()),
+    };
+    f()
+}
+
+// Restores the previous native processor and invoke context when dropped,
+// including when the instruction panics.
+struct ProcessorGuard {
+    processor: Option<ProcessInstruction>,
+    invoke_context: *mut (),
+}
+
+impl Drop for ProcessorGuard {
+    fn drop(&mut self) {
+        PROCESSOR.with(|processor| processor.set(self.processor));
+        INVOKE_CONTEXT.with(|invoke_context| invoke_context.set(self.invoke_context));
+    }
+}
+
+// Pops the innermost serialized parameters buffer when dropped, including
+// when the instruction panics.
+struct ParametersGuard;
+
+impl Drop for ParametersGuard {
+    fn drop(&mut self) {
+        PARAMETERS.with(|parameters| parameters.borrow_mut().pop());
+    }
+}
+
+// Write an account info's owner into the serialized parameters buffer it was
+// deserialized from, as the owner is only borrowed immutably by the account
+// info.
+fn write_owner(account_info: &AccountInfo, owner: &Pubkey) {
+    let address = account_info.owner as *const Pubkey as usize;
+    let (base, offset) = PARAMETERS
+        .with(|parameters| {
+            parameters.borrow().iter().rev().find_map(|(base, len)| {
+                let offset = address.checked_sub(*base as usize)?;
+                (offset.checked_add(size_of::<Pubkey>())? <= *len).then_some((*base, offset))
+            })
+        })
+        .expect("Account info owner is not in a serialized parameters buffer");
+    // SAFETY: The owner lies within a serialized parameters buffer, which is
+    // written through the buffer's own mutable pointer while the instruction
+    // it was serialized for runs.
+    unsafe { base.add(offset).cast::<Pubkey>().write_unaligned(*owner) };
+}
+
+pub use entrypoint::Entrypoint;
+
+// The builtin entrypoint of the native program under test. The struct
+// declared by the macro can't be documented.
+#[allow(missing_docs)]
+mod entrypoint {
+    use super::*;
+
+    declare_process_instruction!(Entrypoint, 1, |invoke_context| {
+        let process_instruction = PROCESSOR
+            .with(|processor| processor.get())
+            .ok_or(InstructionError::UnsupportedProgramId)?;
+        invoke_native_processor(process_instruction, invoke_context)
+    });
+}
+
+fn invoke_native_processor(
+    process_instruction: ProcessInstruction,
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    set_invoke_context(invoke_context);
+
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let instruction_data = instruction_context.get_instruction_data();
+    let instruction_account_indices = 0..instruction_context.get_number_of_instruction_accounts();
+
+    let log_collector = invoke_context.get_log_collector();
+    let program_id = instruction_context.get_last_program_key(transaction_context)?;
+    stable_log::program_invoke(
+        &log_collector,
+        program_id,
+        invoke_context.get_stack_height(),
+    );
+
+    // Duplicate instruction accounts are only committed once.
+    let deduplicated_indices: HashSet<IndexOfAccount> = instruction_account_indices.collect();
+
+    // Serialize the entrypoint parameters with the SBF ABI, as the loader
+    // would, then deserialize them into account infos. There is no VM, so
+    // the account data is always copied.
+    let (mut parameter_bytes, _regions, _accounts_metadata) = serialize_parameters(
+        invoke_context.transaction_context,
+        instruction_context,
+        true,
+        &invoke_context.feature_set,
+    )?;
+    let parameters = parameter_bytes.as_slice_mut();
+    let parameters = (parameters.as_mut_ptr(), parameters.len());
+    PARAMETERS.with(|buffers| buffers.borrow_mut().push(parameters));
+    let _guard = ParametersGuard;
+    // SAFETY: The buffer was just serialized with the SBF ABI, and outlives
+    // the account infos.
+    let (program_id, account_infos, _input) = unsafe { deserialize(parameters.0) };
+
+    process_instruction(program_id, &account_infos, instruction_data).map_err(|err| {
+        let err = InstructionError::from(u64::from(err));
+        stable_log::program_failure(&log_collector, program_id, &err);
+        err
+    })?;
+    stable_log::program_success(&log_collector, program_id);
+
+    let account_info_map: HashMap<_, _> = account_infos.into_iter().map(|a| (a.key, a)).collect();
+
+    // Re-fetch the instruction context, since a cross-program invocation may
+    // have reset the invoke context.
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+
+    // Commit the account info changes back into the transaction context.
+    for index_in_instruction in deduplicated_indices {
+        let mut borrowed_account = instruction_context
+            .try_borrow_instruction_account(transaction_context, index_in_instruction)?;
+        if !borrowed_account.is_writable() {
+            continue;
+        }
+        if let Some(account_info) = account_info_map.get(borrowed_account.get_key()) {
+            if borrowed_account.get_lamports() != account_info.lamports() {
+                borrowed_account
+                    .set_lamports(account_info.lamports(), &invoke_context.feature_set)?;
+            }
+            if borrowed_account
+                .can_data_be_resized(account_info.data_len())
+                .is_ok()
+                && borrowed_account
+                    .can_data_be_changed(&invoke_context.feature_set)
+                    .is_ok()
+            {
+                borrowed_account.set_data_from_slice(
+                    &account_info.data.borrow(),
+                    &invoke_context.feature_set,
+                )?;
+            }
+            // Change the owner last, so the lamports and data may still be
+            // changed beforehand.
+            if borrowed_account.get_owner() != account_info.owner {
+                borrowed_account
+                    .set_owner(account_info.owner.as_ref(), &invoke_context.feature_set)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get_sysvar<T: Default + Sysvar + Sized + Clone>(
+    sysvar: Result<Arc<T>, InstructionError>,
+    var_addr: *mut u8,
+) -> u64 {
+    let invoke_context = get_invoke_context();
+    if invoke_context
+        .consume_checked(
+            invoke_context
+                .get_compute_budget()
+                .sysvar_base_cost
+                .saturating_add(T::size_of() as u64),
+        )
+        .is_err()
+    {
+        panic!("Exceeded compute budget");
+    }
+    match sysvar {
+        // SAFETY: `var_addr` points to a `T`, as passed by `Sysvar::get`.
+        Ok(sysvar) => unsafe {
+            *(var_addr as *mut T) = T::clone(&sysvar);
+            SUCCESS
+        },
+        Err(_) => UNSUPPORTED_SYSVAR,
+    }
+}
+
+struct NativeSyscallStubs;
+
+impl SyscallStubs for NativeSyscallStubs {
+    fn sol_log(&self, message: &str) {
+        let invoke_context = get_invoke_context();
+        ic_msg!(invoke_context, "Program log: {}", message);
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let invoke_context = get_invoke_context();
+        stable_log::program_data(&invoke_context.get_log_collector(), fields);
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        get_invoke_context().get_remaining()
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        let instruction = StableInstruction::from(instruction.clone());
+        let invoke_context = get_invoke_context();
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        let caller = instruction_context
+            .get_last_program_key(transaction_context)
+            .unwrap();
+
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, caller).unwrap())
+            .collect::<Vec<_>>();
+
+        let (instruction_accounts, program_indices) = invoke_context
+            .prepare_instruction(&instruction, &signers)
+            .map_err(|err| ProgramError::try_from(err).unwrap_or_else(|err| panic!("{}", err)))?;
+
+        // Copy the caller's account info changes into the transaction
+        // context.
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        let mut account_indices = Vec::with_capacity(instruction_accounts.len());
+        for instruction_account in instruction_accounts.iter() {
+            let account_key = transaction_context
+                .get_key_of_account_at_index(instruction_account.index_in_transaction)
+                .unwrap();
+            let account_info_index = account_infos
+                .iter()
+                .position(|account_info| account_info.unsigned_key() == account_key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let account_info = &account_infos[account_info_index];
+            let mut borrowed_account = instruction_context
+                .try_borrow_instruction_account(
+                    transaction_context,
+                    instruction_account.index_in_caller,
+                )
+                .unwrap();
+            if borrowed_account.get_lamports() != account_info.lamports() {
+                borrowed_account
+                    .set_lamports(account_info.lamports(), &invoke_context.feature_set)
+                    .unwrap();
+            }
+            let account_info_data = account_info.try_borrow_data().unwrap();
+            match borrowed_account
+                .can_data_be_resized(account_info_data.len())
+                .and_then(|_| borrowed_account.can_data_be_changed(&invoke_context.feature_set))
+            {
+                Ok(()) => borrowed_account
+                    .set_data_from_slice(&account_info_data, &invoke_context.feature_set)
+                    .unwrap(),
+                Err(err) if borrowed_account.get_data() != *account_info_data => {
+                    panic!("{err:?}");
+                }
+                _ => {}
+            }
+            if borrowed_account.get_owner() != account_info.owner {
+                borrowed_account
+                    .set_owner(account_info.owner.as_ref(), &invoke_context.feature_set)
+                    .unwrap();
+            }
+            if instruction_account.is_writable {
+                account_indices.push((instruction_account.index_in_caller, account_info_index));
+            }
+        }
+
+        let mut compute_units_consumed = 0;
+        invoke_context
+            .process_instruction(
+                &instruction.data,
+                &instruction_accounts,
+                &program_indices,
+                &mut compute_units_consumed,
+                &mut ExecuteTimings::default(),
+            )
+            .map_err(|err| ProgramError::try_from(err).unwrap_or_else(|err| panic!("{}", err)))?;
+
+        // Copy the callee's changes back into the caller's account infos.
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        for (index_in_caller, account_info_index) in account_indices {
+            let borrowed_account = instruction_context
+                .try_borrow_instruction_account(transaction_context, index_in_caller)
+                .unwrap();
+            let account_info = &account_infos[account_info_index];
+            **account_info.try_borrow_mut_lamports().unwrap() = borrowed_account.get_lamports();
+            if account_info.owner != borrowed_account.get_owner() {
+                write_owner(account_info, borrowed_account.get_owner());
+            }
+            let new_data = borrowed_account.get_data();
+            if account_info.data_len() != new_data.len() {
+                account_info.realloc(new_data.len(), false)?;
+            }
+            account_info
+                .try_borrow_mut_data()?
+                .clone_from_slice(new_data);
+        }
+
+        Ok(())
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            get_invoke_context().get_sysvar_cache().get_clock(),
+            var_addr,
+        )
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            get_invoke_context().get_sysvar_cache().get_epoch_schedule(),
+            var_addr,
+        )
+    }
+
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            get_invoke_context().get_sysvar_cache().get_epoch_rewards(),
+            var_addr,
+        )
+    }
+
+    #[allow(deprecated)]
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_fees(), var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_rent(), var_addr)
+    }
+
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            get_invoke_context()
+                .get_sysvar_cache()
+                .get_last_restart_slot(),
+            var_addr,
+        )
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        let (program_id, data) = get_invoke_context().transaction_context.get_return_data();
+        Some((*program_id, data.to_vec()))
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let invoke_context = get_invoke_context();
+        let transaction_context = &mut invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        let caller = *instruction_context
+            .get_last_program_key(transaction_context)
+            .unwrap();
+        transaction_context
+            .set_return_data(caller, data.to_vec())
+            .unwrap();
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        get_invoke_context().get_stack_height() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::entrypoint::ProgramResult, std::panic};
+
+    fn process_instruction(_: &Pubkey, _: &[AccountInfo], _: &[u8]) -> ProgramResult {
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_processor_panic() {
+        let result = panic::catch_unwind(|| {
+            with_processor(process_instruction, || {
+                assert!(PROCESSOR.with(|processor| processor.get()).is_some());
+                panic!("Instruction panicked");
+            })
+        });
+        assert!(result.is_err());
+        assert!(PROCESSOR.with(|processor| processor.get()).is_none());
+        assert!(INVOKE_CONTEXT
+            .with(|invoke_context| invoke_context.get())
+            .is_null());
+    }
+}
@@ -2,16 +2,40 @@
 
 use {
     libfuzzer_sys::fuzz_target,
-    protosol::fixture::Fixture,
+    protosol::{fixture::Fixture, harness::Harness},
     std::{env, fs},
 };
 
+const EDGE_COUNTERS_LEN: usize = 1 << 16;
+
+// Edge coverage of the SBF program, which libFuzzer's own instrumentation of
+// the host harness can't see. libFuzzer clears these extra counters before
+// each run, and keeps inputs which reach new program edges.
+#[used]
+#[cfg_attr(target_os = "linux", link_section = "__libfuzzer_extra_counters")]
+static mut EDGE_COUNTERS: [u8; EDGE_COUNTERS_LEN] = [0; EDGE_COUNTERS_LEN];
+
 // Agave program runtime.
 fuzz_target!(|data: &[u8]| {
-    let elf = fs::read(env::var("PROGRAM").expect("Environment variable PROGRAM not set"))
-        .expect("Failed to read program ELF file.");
+    // Without `PROGRAM`, the program is loaded from each fixture's accounts.
+    let elf = env::var("PROGRAM")
+        .ok()
+        .map(|path| fs::read(path).expect("Failed to read program ELF file."));
 
     if let Ok(fixture) = Fixture::decode(data) {
-        protosol::process_fixture(fixture, &elf);
+        let harness = Harness::default().with_coverage();
+        match elf {
+            Some(elf) => harness.process_fixture(fixture, &elf),
+            None => {
+                // Fixtures without valid program accounts are skipped.
+                let _ = harness.process_fixture_from_accounts(fixture);
+            }
+        }
+        if let Some(coverage) = harness.coverage() {
+            // SAFETY: libFuzzer runs one input at a time, and only reads the
+            // counters between runs.
+            let edge_counters = unsafe { &mut *std::ptr::addr_of_mut!(EDGE_COUNTERS) };
+            coverage.update_edge_counters(edge_counters);
+        }
     }
 });
@@ -0,0 +1,63 @@
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    protosol::{
+        account_schema::{AccountSchema, SchemaMutator},
+        fixture::Fixture,
+        fuzz_template::FuzzTemplate,
+        harness::Harness,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{env, fs, str::FromStr, sync::OnceLock},
+};
+
+const EDGE_COUNTERS_LEN: usize = 1 << 16;
+
+// Edge coverage of the SBF program, as in the `agave` target.
+#[used]
+#[cfg_attr(target_os = "linux", link_section = "__libfuzzer_extra_counters")]
+static mut EDGE_COUNTERS: [u8; EDGE_COUNTERS_LEN] = [0; EDGE_COUNTERS_LEN];
+
+// The template fixture from `TEMPLATE`, with the accounts listed in the
+// comma-separated `MUTABLE_ACCOUNTS` mutated too, field by field if `IDL`
+// names an Anchor IDL describing them, and the program ELF from `PROGRAM`.
+fn setup() -> &'static (FuzzTemplate, Vec<u8>) {
+    static SETUP: OnceLock<(FuzzTemplate, Vec<u8>)> = OnceLock::new();
+    SETUP.get_or_init(|| {
+        let blob = fs::read(env::var("TEMPLATE").expect("TEMPLATE must be set."))
+            .expect("Failed to read template fixture file.");
+        let fixture = Fixture::decode(&blob).expect("Invalid template fixture.");
+        let mutable_accounts = env::var("MUTABLE_ACCOUNTS")
+            .map(|accounts| {
+                accounts
+                    .split(',')
+                    .map(|pubkey| Pubkey::from_str(pubkey.trim()).expect("Invalid pubkey."))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut template = FuzzTemplate::new(fixture, &mutable_accounts)
+            .expect("Mutable accounts must be template accounts.");
+        if let Ok(path) = env::var("IDL") {
+            let idl = fs::read_to_string(path).expect("Failed to read IDL file.");
+            let schemas = AccountSchema::from_anchor_idl(&idl).expect("Invalid IDL.");
+            template = template.with_schema_mutator(SchemaMutator::new(schemas));
+        }
+        let elf = fs::read(env::var("PROGRAM").expect("PROGRAM must be set."))
+            .expect("Failed to read program ELF file.");
+        (template, elf)
+    })
+}
+
+// Agave program runtime, fuzzing the instruction data of a template fixture.
+fuzz_target!(|data: &[u8]| {
+    let (template, elf) = setup();
+    let harness = Harness::default().with_coverage();
+    harness.execute_instruction(template.instantiate(data), elf);
+    if let Some(coverage) = harness.coverage() {
+        // SAFETY: libFuzzer runs one input at a time, and only reads the
+        // counters between runs.
+        let edge_counters = unsafe { &mut *std::ptr::addr_of_mut!(EDGE_COUNTERS) };
+        coverage.update_edge_counters(edge_counters);
+    }
+});
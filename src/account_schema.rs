@@ -0,0 +1,607 @@
+//! Schema-aware account data mutation, for fuzzing.
+//!
+//! Random bytes mostly break account deserialization, so account layouts
+//! can be described by schemas of typed fields, built field by field in
+//! Borsh order or parsed from an Anchor IDL. Mutations then act on whole
+//! fields: numbers are set to boundary values, public keys are swapped
+//! among known keys, and discriminators are left intact.
+
+use {
+    serde_json::Value,
+    solana_sdk::{hash::hashv, pubkey::Pubkey},
+    thiserror::Error,
+};
+
+/// Errors possible for parsing account schemas.
+#[derive(Debug, Error, PartialEq)]
+pub enum AccountSchemaError {
+    /// The IDL is not valid JSON.
+    #[error("Invalid IDL JSON: {0}")]
+    InvalidJson(String),
+    /// The IDL is missing a required entry, or an entry is malformed.
+    #[error("Invalid IDL: {0}")]
+    InvalidIdl(String),
+}
+
+/// The type of an account field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    /// A boolean, one byte.
+    Bool,
+    /// An unsigned 8-bit integer.
+    U8,
+    /// An unsigned 16-bit integer, little-endian.
+    U16,
+    /// An unsigned 32-bit integer, little-endian.
+    U32,
+    /// An unsigned 64-bit integer, little-endian.
+    U64,
+    /// An unsigned 128-bit integer, little-endian.
+    U128,
+    /// A signed 8-bit integer.
+    I8,
+    /// A signed 16-bit integer, little-endian.
+    I16,
+    /// A signed 32-bit integer, little-endian.
+    I32,
+    /// A signed 64-bit integer, little-endian.
+    I64,
+    /// A signed 128-bit integer, little-endian.
+    I128,
+    /// A public key.
+    Pubkey,
+    /// Opaque bytes of a fixed length.
+    Bytes(usize),
+}
+
+impl FieldType {
+    /// The size of the field in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Bool | Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 => 4,
+            Self::U64 | Self::I64 => 8,
+            Self::U128 | Self::I128 => 16,
+            Self::Pubkey => 32,
+            Self::Bytes(len) => *len,
+        }
+    }
+
+    // The type of a fixed-size primitive IDL type.
+    fn from_idl_primitive(name: &str) -> Option<Self> {
+        match name {
+            "bool" => Some(Self::Bool),
+            "u8" => Some(Self::U8),
+            "u16" => Some(Self::U16),
+            "u32" => Some(Self::U32),
+            "u64" => Some(Self::U64),
+            "u128" => Some(Self::U128),
+            "i8" => Some(Self::I8),
+            "i16" => Some(Self::I16),
+            "i32" => Some(Self::I32),
+            "i64" => Some(Self::I64),
+            "i128" => Some(Self::I128),
+            "pubkey" | "publicKey" => Some(Self::Pubkey),
+            _ => None,
+        }
+    }
+}
+
+/// A field of an account layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountField {
+    /// The field name, with the names of enclosing structs for nested
+    /// fields, such as `config.fee`.
+    pub name: String,
+    /// The offset of the field in the account data.
+    pub offset: usize,
+    /// The field type.
+    pub field_type: FieldType,
+}
+
+/// The layout of an account type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountSchema {
+    /// The account type name.
+    pub name: String,
+    /// The discriminator the account data begins with, if any.
+    pub discriminator: Vec<u8>,
+    /// The fields of the account data.
+    pub fields: Vec<AccountField>,
+}
+
+impl AccountSchema {
+    /// Create a schema with no fields, for account data beginning with a
+    /// discriminator, which may be empty.
+    pub fn new(name: &str, discriminator: &[u8]) -> Self {
+        Self {
+            name: name.to_string(),
+            discriminator: discriminator.to_vec(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field following the last field, or the discriminator, as Borsh
+    /// lays out fixed-size fields. A field which would end beyond the
+    /// addressable data is left out.
+    pub fn with_field(mut self, name: &str, field_type: FieldType) -> Self {
+        self.push_field(name, field_type);
+        self
+    }
+
+    // Add a field following the last field, returning whether its end is
+    // addressable.
+    fn push_field(&mut self, name: &str, field_type: FieldType) -> bool {
+        let offset = self.len();
+        if offset.checked_add(field_type.size()).is_none() {
+            return false;
+        }
+        self.fields.push(AccountField {
+            name: name.to_string(),
+            offset,
+            field_type,
+        });
+        true
+    }
+
+    /// Add a field at an offset in the account data.
+    pub fn with_field_at(mut self, name: &str, offset: usize, field_type: FieldType) -> Self {
+        self.fields.push(AccountField {
+            name: name.to_string(),
+            offset,
+            field_type,
+        });
+        self
+    }
+
+    /// The length of the data described, up to the end of the last field.
+    pub fn len(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.offset.saturating_add(field.field_type.size()))
+            .max()
+            .unwrap_or_default()
+            .max(self.discriminator.len())
+    }
+
+    /// Whether the schema describes no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether account data has this schema's layout: it begins with the
+    /// discriminator, and is long enough for every field.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.starts_with(&self.discriminator) && data.len() >= self.len()
+    }
+
+    /// Parse the schemas of the account types of an Anchor IDL, in either
+    /// the current format, with explicit discriminators, or the legacy
+    /// format, with discriminators derived from the account names.
+    ///
+    /// Fields are described up to the first field without a fixed size,
+    /// such as a string, a vector or an enum, after which offsets depend on
+    /// the data. Fields of nested structs are flattened.
+    pub fn from_anchor_idl(idl: &str) -> Result<Vec<Self>, AccountSchemaError> {
+        let idl: Value = serde_json::from_str(idl)
+            .map_err(|error| AccountSchemaError::InvalidJson(error.to_string()))?;
+        let types = idl
+            .get("types")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let accounts = idl
+            .get("accounts")
+            .and_then(Value::as_array)
+            .ok_or_else(|| AccountSchemaError::InvalidIdl(String::from("missing accounts")))?;
+
+        accounts
+            .iter()
+            .map(|account| {
+                let name = account.get("name").and_then(Value::as_str).ok_or_else(|| {
+                    AccountSchemaError::InvalidIdl(String::from("account without a name"))
+                })?;
+                let discriminator = match account.get("discriminator") {
+                    Some(discriminator) => discriminator
+                        .as_array()
+                        .and_then(|bytes| {
+                            bytes
+                                .iter()
+                                .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                                .collect::<Option<Vec<_>>>()
+                        })
+                        .ok_or_else(|| {
+                            AccountSchemaError::InvalidIdl(format!(
+                                "invalid discriminator of account {}",
+                                name
+                            ))
+                        })?,
+                    None => {
+                        hashv(&[format!("account:{}", name).as_bytes()]).to_bytes()[..8].to_vec()
+                    }
+                };
+                // Legacy IDLs define the account type in place.
+                let type_definition = account
+                    .get("type")
+                    .or_else(|| type_definition(types, name))
+                    .ok_or_else(|| {
+                        AccountSchemaError::InvalidIdl(format!("missing type of account {}", name))
+                    })?;
+
+                let mut schema = Self::new(name, &discriminator);
+                add_struct_fields(&mut schema, "", type_definition, types, 0);
+                Ok(schema)
+            })
+            .collect()
+    }
+}
+
+// The definition of a named type of an IDL.
+fn type_definition<'a>(types: &'a [Value], name: &str) -> Option<&'a Value> {
+    types
+        .iter()
+        .find(|definition| definition.get("name").and_then(Value::as_str) == Some(name))
+        .and_then(|definition| definition.get("type"))
+}
+
+// Add the fields of an IDL struct type to a schema, returning whether every
+// field has a fixed size. Nesting is bounded, in case of recursive types.
+fn add_struct_fields(
+    schema: &mut AccountSchema,
+    prefix: &str,
+    definition: &Value,
+    types: &[Value],
+    depth: usize,
+) -> bool {
+    if depth > 8 || definition.get("kind").and_then(Value::as_str) != Some("struct") {
+        return false;
+    }
+    let Some(fields) = definition.get("fields").and_then(Value::as_array) else {
+        return false;
+    };
+    for field in fields {
+        let (Some(name), Some(field_type)) =
+            (field.get("name").and_then(Value::as_str), field.get("type"))
+        else {
+            return false;
+        };
+        let name = format!("{}{}", prefix, name);
+        if !add_field(schema, &name, field_type, types, depth) {
+            return false;
+        }
+    }
+    true
+}
+
+// Add a field of an IDL type to a schema, returning whether it has a fixed
+// size.
+fn add_field(
+    schema: &mut AccountSchema,
+    name: &str,
+    field_type: &Value,
+    types: &[Value],
+    depth: usize,
+) -> bool {
+    if let Some(field_type) = field_type.as_str().and_then(FieldType::from_idl_primitive) {
+        return schema.push_field(name, field_type);
+    }
+    if let Some([element, len]) = field_type
+        .get("array")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+    {
+        let (Some(element), Some(len)) = (
+            element.as_str().and_then(FieldType::from_idl_primitive),
+            len.as_u64().and_then(|len| usize::try_from(len).ok()),
+        ) else {
+            return false;
+        };
+        // Arrays too large for any account have no usable layout.
+        let Some(size) = element.size().checked_mul(len) else {
+            return false;
+        };
+        return schema.push_field(name, FieldType::Bytes(size));
+    }
+    // Named types are referenced by name in legacy IDLs, and by an object
+    // with the name in current IDLs.
+    let defined = field_type.get("defined").and_then(|defined| {
+        defined
+            .as_str()
+            .or_else(|| defined.get("name").and_then(Value::as_str))
+    });
+    match defined.and_then(|defined| type_definition(types, defined)) {
+        Some(definition) => {
+            add_struct_fields(schema, &format!("{}.", name), definition, types, depth + 1)
+        }
+        None => false,
+    }
+}
+
+/// Mutates account data field by field, according to account schemas.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaMutator {
+    schemas: Vec<AccountSchema>,
+    known_keys: Vec<Pubkey>,
+}
+
+impl SchemaMutator {
+    /// Create a mutator for accounts with any of the schemas.
+    pub fn new(schemas: Vec<AccountSchema>) -> Self {
+        Self {
+            schemas,
+            known_keys: Vec::new(),
+        }
+    }
+
+    /// Add known public keys, which public key fields are swapped among.
+    pub fn with_known_keys(mut self, known_keys: &[Pubkey]) -> Self {
+        self.known_keys.extend_from_slice(known_keys);
+        self
+    }
+
+    /// The schema of account data, the first whose layout it has.
+    pub fn schema(&self, data: &[u8]) -> Option<&AccountSchema> {
+        self.schemas.iter().find(|schema| schema.matches(data))
+    }
+
+    /// Mutate account data with a schema, driven by fuzz input consumed in
+    /// pairs of bytes: the first chooses a field, the second a mutation.
+    /// Data without a schema is left unchanged.
+    pub fn mutate(&self, data: &mut [u8], input: &[u8]) {
+        for pair in input.chunks_exact(2) {
+            self.mutate_field(data, pair[0], pair[1]);
+        }
+    }
+
+    /// Apply one mutation to a field of account data with a schema.
+    pub(crate) fn mutate_field(&self, data: &mut [u8], field: u8, mutation: u8) {
+        let Some(schema) = self.schema(data) else {
+            return;
+        };
+        // Fields overlapping the discriminator are never mutated.
+        let fields = schema
+            .fields
+            .iter()
+            .filter(|field| field.offset >= schema.discriminator.len())
+            .collect::<Vec<_>>();
+        if fields.is_empty() {
+            return;
+        }
+        let AccountField {
+            offset, field_type, ..
+        } = fields[field as usize % fields.len()];
+        let bytes = &mut data[*offset..*offset + field_type.size()];
+
+        match field_type {
+            FieldType::Bool => bytes[0] = mutation & 1,
+            FieldType::Pubkey => {
+                if !self.known_keys.is_empty() {
+                    let key = self.known_keys[mutation as usize % self.known_keys.len()];
+                    bytes.copy_from_slice(key.as_ref());
+                }
+            }
+            FieldType::Bytes(_) => bytes.fill(mutation),
+            _ => mutate_integer(bytes, mutation),
+        }
+    }
+}
+
+// Set a little-endian integer to a boundary value. The same bit patterns are
+// boundaries whether the integer is signed or not: zero, one, all ones (-1
+// or MAX), MAX - 1 or -2, the sign bit (MIN or the midpoint) and the bit
+// below it (MAX or the midpoint - 1), or one more or less than the value.
+fn mutate_integer(bytes: &mut [u8], mutation: u8) {
+    let bits = bytes.len() as u32 * 8;
+    let mask = u128::MAX >> (128 - bits);
+    let sign_bit = 1u128 << (bits - 1);
+    let mut value = [0; 16];
+    value[..bytes.len()].copy_from_slice(bytes);
+    let value = u128::from_le_bytes(value);
+
+    let mutated = match mutation % 8 {
+        0 => 0,
+        1 => 1,
+        2 => mask,
+        3 => mask - 1,
+        4 => sign_bit,
+        5 => sign_bit - 1,
+        6 => value.wrapping_add(1) & mask,
+        _ => value.wrapping_sub(1) & mask,
+    };
+    bytes.copy_from_slice(&mutated.to_le_bytes()[..bytes.len()]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_schema() -> AccountSchema {
+        AccountSchema::new("Vault", &[7; 8])
+            .with_field("authority", FieldType::Pubkey)
+            .with_field("amount", FieldType::U64)
+            .with_field("locked", FieldType::Bool)
+    }
+
+    #[test]
+    fn test_layout() {
+        let schema = vault_schema();
+        assert_eq!(
+            schema
+                .fields
+                .iter()
+                .map(|field| field.offset)
+                .collect::<Vec<_>>(),
+            vec![8, 40, 48]
+        );
+        assert_eq!(schema.len(), 49);
+
+        let mut data = vec![7; 49];
+        assert!(schema.matches(&data));
+        data[0] = 0;
+        assert!(!schema.matches(&data));
+        assert!(!schema.matches(&[7; 48]));
+    }
+
+    #[test]
+    fn test_mutate_integer() {
+        let mutations = (0..8)
+            .map(|mutation| {
+                let mut bytes = 5i16.to_le_bytes();
+                mutate_integer(&mut bytes, mutation);
+                i16::from_le_bytes(bytes)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(mutations, vec![0, 1, -1, -2, i16::MIN, i16::MAX, 6, 4]);
+
+        let mut bytes = u128::MAX.to_le_bytes();
+        mutate_integer(&mut bytes, 6);
+        assert_eq!(u128::from_le_bytes(bytes), 0);
+    }
+
+    #[test]
+    fn test_mutate() {
+        let key = Pubkey::new_unique();
+        let mutator = SchemaMutator::new(vec![vault_schema()]).with_known_keys(&[key]);
+
+        let mut data = vec![7; 8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&[0, 42]);
+
+        mutator.mutate(&mut data, &[0, 0, 1, 2, 2, 3, 1]);
+        assert_eq!(&data[..8], &[7; 8]);
+        assert_eq!(&data[8..40], key.as_ref());
+        assert_eq!(&data[40..48], &u64::MAX.to_le_bytes());
+        assert_eq!(&data[48..], &[1, 42]);
+
+        // Data without a schema is left unchanged.
+        let mut data = vec![0; 49];
+        mutator.mutate(&mut data, &[0, 0, 1, 2]);
+        assert_eq!(data, vec![0; 49]);
+    }
+
+    #[test]
+    fn test_from_anchor_idl() {
+        let idl = r#"{
+            "accounts": [
+                { "name": "Vault", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] },
+                {
+                    "name": "Counter",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "count", "type": "u32" },
+                            { "name": "label", "type": "string" },
+                            { "name": "after", "type": "u8" }
+                        ]
+                    }
+                }
+            ],
+            "types": [
+                {
+                    "name": "Vault",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "authority", "type": "pubkey" },
+                            { "name": "config", "type": { "defined": { "name": "Config" } } },
+                            { "name": "seed", "type": { "array": ["u8", 4] } }
+                        ]
+                    }
+                },
+                {
+                    "name": "Config",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "fee", "type": "u16" },
+                            { "name": "paused", "type": "bool" }
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let schemas = AccountSchema::from_anchor_idl(idl).unwrap();
+        assert_eq!(
+            schemas[0],
+            AccountSchema::new("Vault", &[1, 2, 3, 4, 5, 6, 7, 8])
+                .with_field("authority", FieldType::Pubkey)
+                .with_field("config.fee", FieldType::U16)
+                .with_field("config.paused", FieldType::Bool)
+                .with_field("seed", FieldType::Bytes(4))
+        );
+        // Legacy discriminators are derived from the name, and fields end at
+        // the first without a fixed size.
+        assert_eq!(
+            schemas[1],
+            AccountSchema::new("Counter", &hashv(&[b"account:Counter"]).to_bytes()[..8])
+                .with_field("count", FieldType::U32)
+        );
+
+        // Fields end at an array whose size overflows.
+        let idl = r#"{
+            "accounts": [
+                {
+                    "name": "Huge",
+                    "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "count", "type": "u32" },
+                            { "name": "items", "type": { "array": ["u64", 18446744073709551615] } },
+                            { "name": "after", "type": "u8" }
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        assert_eq!(
+            AccountSchema::from_anchor_idl(idl).unwrap(),
+            vec![AccountSchema::new("Huge", &[1, 2, 3, 4, 5, 6, 7, 8])
+                .with_field("count", FieldType::U32)]
+        );
+
+        // Fields end at the first field whose end overflows, even after an
+        // array filling the rest of the addressable data.
+        let idl = format!(
+            r#"{{
+                "accounts": [
+                    {{
+                        "name": "Full",
+                        "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                        "type": {{
+                            "kind": "struct",
+                            "fields": [
+                                {{ "name": "count", "type": "u32" }},
+                                {{ "name": "items", "type": {{ "array": ["u8", {}] }} }},
+                                {{ "name": "after", "type": "u8" }}
+                            ]
+                        }}
+                    }}
+                ]
+            }}"#,
+            usize::MAX - 12
+        );
+        let schemas = AccountSchema::from_anchor_idl(&idl).unwrap();
+        assert_eq!(
+            schemas,
+            vec![AccountSchema::new("Full", &[1, 2, 3, 4, 5, 6, 7, 8])
+                .with_field("count", FieldType::U32)
+                .with_field("items", FieldType::Bytes(usize::MAX - 12))]
+        );
+        assert_eq!(schemas[0].len(), usize::MAX);
+        assert!(!schemas[0].matches(&[1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0]));
+
+        assert!(matches!(
+            AccountSchema::from_anchor_idl("{"),
+            Err(AccountSchemaError::InvalidJson(_))
+        ));
+        assert_eq!(
+            AccountSchema::from_anchor_idl("{}"),
+            Err(AccountSchemaError::InvalidIdl(String::from(
+                "missing accounts"
+            )))
+        );
+    }
+}
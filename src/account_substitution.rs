@@ -0,0 +1,283 @@
+//! Account substitution scanning, for detecting missing owner, address and
+//! type checks.
+//!
+//! A passing fixture is executed again once for each substitution of its
+//! instruction's accounts: an account owned by another program, two accounts
+//! swapped between positions, or an impostor account at another address with
+//! the same owner and data. A substitution which still succeeds indicates the
+//! program may accept confused accounts, such as a missing owner, PDA or
+//! discriminator check.
+
+use {
+    crate::{
+        fixture::{context::FixtureContext, error::FixtureError},
+        harness::Harness,
+        result::ExecutionResult,
+    },
+    solana_sdk::{
+        account::{ReadableAccount, WritableAccount},
+        pubkey::Pubkey,
+    },
+};
+
+/// The owner assigned to accounts by owner substitutions, a program which
+/// doesn't exist.
+pub const IMPOSTOR_OWNER: Pubkey = Pubkey::new_from_array([0xee; 32]);
+
+/// A substitution of a fixture's instruction accounts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AccountSubstitution {
+    /// The account is owned by `IMPOSTOR_OWNER` instead.
+    Owner {
+        /// The account's public key.
+        pubkey: Pubkey,
+    },
+    /// The accounts at two instruction account positions are swapped,
+    /// keeping each position's signer and writable flags.
+    Swap {
+        /// The first instruction account index.
+        first_index: usize,
+        /// The second instruction account index.
+        second_index: usize,
+    },
+    /// The account is replaced by an impostor at another address, with the
+    /// same lamports, data and owner.
+    Impostor {
+        /// The account's public key.
+        pubkey: Pubkey,
+        /// The impostor's public key.
+        impostor: Pubkey,
+    },
+}
+
+impl AccountSubstitution {
+    fn apply(&self, context: &mut FixtureContext) {
+        match self {
+            Self::Owner { pubkey } => context
+                .accounts
+                .iter_mut()
+                .filter(|(key, _)| key == pubkey)
+                .for_each(|(_, account)| account.set_owner(IMPOSTOR_OWNER)),
+            Self::Swap {
+                first_index,
+                second_index,
+            } => {
+                let first = context.instruction_accounts[*first_index].pubkey;
+                let second = context.instruction_accounts[*second_index].pubkey;
+                context.instruction_accounts[*first_index].pubkey = second;
+                context.instruction_accounts[*second_index].pubkey = first;
+            }
+            Self::Impostor { pubkey, impostor } => {
+                if let Some((_, account)) = context.accounts.iter().find(|(key, _)| key == pubkey) {
+                    context.accounts.push((*impostor, account.clone()));
+                }
+                context
+                    .instruction_accounts
+                    .iter_mut()
+                    .filter(|meta| meta.pubkey == *pubkey)
+                    .for_each(|meta| meta.pubkey = *impostor);
+            }
+        }
+    }
+}
+
+/// A substitution with which the fixture's instruction still succeeds.
+#[derive(Debug)]
+pub struct AcceptedSubstitution {
+    /// The account substitution.
+    pub substitution: AccountSubstitution,
+    /// The result of executing the fixture with the substitution.
+    pub result: ExecutionResult,
+}
+
+/// The report of an account substitution scan.
+#[derive(Debug)]
+pub struct AccountSubstitutionReport {
+    /// The result of executing the fixture with its own accounts.
+    pub baseline: ExecutionResult,
+    /// The number of substitutions executed, excluding the baseline.
+    pub substitutions_executed: usize,
+    /// The substitutions with which the instruction still succeeds.
+    pub accepted_substitutions: Vec<AcceptedSubstitution>,
+}
+
+fn account_substitutions(context: &FixtureContext) -> Vec<AccountSubstitution> {
+    // The first position of each account passed to the instruction, other
+    // than the program under test and executable accounts.
+    let mut positions: Vec<(usize, Pubkey)> = Vec::new();
+    for (index, meta) in context.instruction_accounts.iter().enumerate() {
+        let executable = context
+            .accounts
+            .iter()
+            .find(|(key, _)| *key == meta.pubkey)
+            .map_or(true, |(_, account)| account.executable());
+        if meta.pubkey != context.program_id
+            && !executable
+            && !positions.iter().any(|(_, pubkey)| *pubkey == meta.pubkey)
+        {
+            positions.push((index, meta.pubkey));
+        }
+    }
+
+    let mut substitutions = Vec::new();
+    for (_, pubkey) in &positions {
+        substitutions.push(AccountSubstitution::Owner { pubkey: *pubkey });
+    }
+    for (i, (first_index, _)) in positions.iter().enumerate() {
+        for (second_index, _) in &positions[i + 1..] {
+            substitutions.push(AccountSubstitution::Swap {
+                first_index: *first_index,
+                second_index: *second_index,
+            });
+        }
+    }
+    for (_, pubkey) in &positions {
+        let impostor = Pubkey::create_with_seed(pubkey, "impostor", &IMPOSTOR_OWNER)
+            .expect("Valid seed and owner");
+        substitutions.push(AccountSubstitution::Impostor {
+            pubkey: *pubkey,
+            impostor,
+        });
+    }
+    substitutions
+}
+
+/// Execute a passing fixture's instruction with the harness, with its own
+/// accounts, then once for each substitution of its instruction's accounts,
+/// and report the substitutions with which the instruction still succeeds.
+///
+/// Every account passed to the instruction, other than the program under
+/// test and executable accounts, is substituted with an account owned by
+/// `IMPOSTOR_OWNER`, with each other such account, and with an impostor.
+///
+/// Fails with `FixtureError::BaselineFailed` if the fixture's own
+/// instruction fails.
+pub fn run_account_substitution_scan(
+    harness: &Harness,
+    context: &FixtureContext,
+    elf: &[u8],
+) -> Result<AccountSubstitutionReport, FixtureError> {
+    let substitutions = account_substitutions(context);
+    let substitutions_executed = substitutions.len();
+    let (baseline, accepted_substitutions) = harness.scan_variants(
+        context,
+        elf,
+        substitutions,
+        AccountSubstitution::apply,
+        |result, _| result.result.is_ok(),
+    )?;
+
+    Ok(AccountSubstitutionReport {
+        baseline,
+        substitutions_executed,
+        accepted_substitutions: accepted_substitutions
+            .into_iter()
+            .map(|(substitution, result)| AcceptedSubstitution {
+                substitution,
+                result,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::fixture::{context::ProgramStatus, sysvars::FixtureSysvarContext},
+        solana_sdk::{
+            account::{Account, AccountSharedData},
+            bpf_loader_upgradeable,
+            feature_set::FeatureSet,
+            instruction::AccountMeta,
+        },
+    };
+
+    #[test]
+    fn test_account_substitutions() {
+        let program_id = Pubkey::new_unique();
+        let state = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let executable = Pubkey::new_unique();
+
+        let state_account = AccountSharedData::from(Account {
+            lamports: 1_000_000,
+            data: vec![1, 2, 3, 4],
+            owner: program_id,
+            ..Account::default()
+        });
+        let mut context = FixtureContext {
+            program_id,
+            loader_id: bpf_loader_upgradeable::id(),
+            feature_set: FeatureSet::all_enabled(),
+            sysvar_context: FixtureSysvarContext::default(),
+            accounts: vec![
+                (state, state_account.clone()),
+                (authority, AccountSharedData::default()),
+                (
+                    executable,
+                    AccountSharedData::from(Account {
+                        executable: true,
+                        ..Account::default()
+                    }),
+                ),
+            ],
+            instruction_accounts: vec![
+                AccountMeta::new(state, false),
+                AccountMeta::new_readonly(executable, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new_readonly(program_id, false),
+                AccountMeta::new(state, false),
+            ],
+            instruction_data: vec![],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
+        };
+
+        let substitutions = account_substitutions(&context);
+        let impostor = |pubkey: &Pubkey| {
+            Pubkey::create_with_seed(pubkey, "impostor", &IMPOSTOR_OWNER).unwrap()
+        };
+        assert_eq!(
+            substitutions,
+            vec![
+                AccountSubstitution::Owner { pubkey: state },
+                AccountSubstitution::Owner { pubkey: authority },
+                AccountSubstitution::Swap {
+                    first_index: 0,
+                    second_index: 2,
+                },
+                AccountSubstitution::Impostor {
+                    pubkey: state,
+                    impostor: impostor(&state),
+                },
+                AccountSubstitution::Impostor {
+                    pubkey: authority,
+                    impostor: impostor(&authority),
+                },
+            ]
+        );
+
+        let mut owner_substituted = context.clone();
+        substitutions[0].apply(&mut owner_substituted);
+        assert_eq!(owner_substituted.accounts[0].1.owner(), &IMPOSTOR_OWNER);
+
+        let mut swapped = context.clone();
+        substitutions[2].apply(&mut swapped);
+        assert_eq!(
+            swapped.instruction_accounts[0],
+            AccountMeta::new(authority, false)
+        );
+        assert_eq!(
+            swapped.instruction_accounts[2],
+            AccountMeta::new_readonly(state, true)
+        );
+
+        // Every position of the account is replaced by the impostor.
+        substitutions[3].apply(&mut context);
+        assert_eq!(context.accounts[3], (impostor(&state), state_account));
+        assert_eq!(context.instruction_accounts[0].pubkey, impostor(&state));
+        assert_eq!(context.instruction_accounts[4].pubkey, impostor(&state));
+    }
+}
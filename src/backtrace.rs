@@ -0,0 +1,293 @@
+//! Source-level backtraces of SBF program failures.
+//!
+//! The faulting program counter and call-frame stack are recovered from the
+//! VM's instruction trace of the failed execution, with frames found from the
+//! frame pointer as in the profiler. Program counters are symbolized through
+//! the ELF's DWARF debug info, including inlined functions, or its symbol
+//! table without debug info. The location passed to `sol_panic_` is recovered
+//! from the program's failure log.
+
+use {
+    crate::{
+        profiler::{demangle, ElfSymbols},
+        trace::{walk_frames, INSN_SIZE},
+    },
+    addr2line::{
+        gimli::{EndianRcSlice, RunTimeEndian},
+        object::{File, Object, ObjectSection},
+        Context,
+    },
+    std::fmt,
+};
+
+// The opcode which returns from a function, or exits the program from the
+// outermost frame.
+const EXIT_OPCODE: u8 = 0x95;
+
+// The failure logged by the runtime when a program calls `sol_panic_`.
+const PANIC_LOG_PREFIX: &str = "SBF program Panicked in ";
+
+/// A backtrace of an SBF program failure.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Backtrace {
+    /// The location the program panicked at, if it called `sol_panic_`.
+    pub panic_location: Option<PanicLocation>,
+    /// The call frames at the fault, innermost first, with inlined
+    /// functions as frames of their own.
+    pub frames: Vec<BacktraceFrame>,
+}
+
+/// A source location passed to `sol_panic_`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanicLocation {
+    /// The source file.
+    pub file: String,
+    /// The line in the source file.
+    pub line: u64,
+    /// The column in the line.
+    pub column: u64,
+}
+
+/// A call frame of a backtrace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BacktraceFrame {
+    /// The program counter, the instruction's index in the text section.
+    /// For callers, this is the call instruction.
+    pub pc: u64,
+    /// The demangled function name.
+    pub function: String,
+    /// The source file, if known from the debug info.
+    pub file: Option<String>,
+    /// The line in the source file, if known from the debug info.
+    pub line: Option<u32>,
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(PanicLocation { file, line, column }) = &self.panic_location {
+            writeln!(f, "panicked at {}:{}:{}", file, line, column)?;
+        }
+        writeln!(f, "backtrace:")?;
+        for (index, frame) in self.frames.iter().enumerate() {
+            write!(f, "{:>4}: {:#x} {}", index, frame.pc, frame.function)?;
+            match (&frame.file, frame.line) {
+                (Some(file), Some(line)) => writeln!(f, " at {}:{}", file, line)?,
+                (Some(file), None) => writeln!(f, " at {}", file)?,
+                _ => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Parse the location passed to `sol_panic_` from a program's failure log.
+fn panic_location(logs: &[String]) -> Option<PanicLocation> {
+    logs.iter().find_map(|log| {
+        let (_, location) = log.split_once(PANIC_LOG_PREFIX)?;
+        let (file, position) = location.rsplit_once(" at ")?;
+        let (line, column) = position.split_once(':')?;
+        Some(PanicLocation {
+            file: file.to_string(),
+            line: line.parse().ok()?,
+            column: column.parse().ok()?,
+        })
+    })
+}
+
+// The program counters of the call frames at the end of an execution's
+// trace, innermost first, or `None` if the program exited normally.
+fn fault_stack(trace: &[[u64; 12]], text: &[u8]) -> Option<Vec<u64>> {
+    // The last program counter executed in each frame.
+    let frames = walk_frames(
+        trace,
+        |pc| pc,
+        |frames, pc| {
+            *frames.last_mut().unwrap() = pc;
+        },
+    );
+    let last_pc = frames.last()?;
+    let opcode = text.get((last_pc * INSN_SIZE) as usize);
+    if frames.len() == 1 && opcode == Some(&EXIT_OPCODE) {
+        return None;
+    }
+    Some(frames.into_iter().rev().collect())
+}
+
+// Symbolize a program counter as its frames, with inlined functions
+// innermost first.
+fn symbolize(
+    pc: u64,
+    text_address: u64,
+    context: Option<&Context<EndianRcSlice<RunTimeEndian>>>,
+    symbols: Option<&ElfSymbols>,
+) -> Vec<BacktraceFrame> {
+    let address = text_address + pc * INSN_SIZE;
+    let mut frames = Vec::new();
+    let inlined_frames = context.map(|context| context.find_frames(address).skip_all_loads());
+    if let Some(Ok(mut inlined_frames)) = inlined_frames {
+        while let Ok(Some(frame)) = inlined_frames.next() {
+            let function = frame
+                .function
+                .as_ref()
+                .and_then(|function| function.raw_name().ok().map(|name| demangle(&name)));
+            let symbol_name = || symbols.map(|symbols| symbols.function_name(pc));
+            frames.push(BacktraceFrame {
+                pc,
+                function: function
+                    .or_else(symbol_name)
+                    .unwrap_or_else(|| format!("function_{:#x}", pc)),
+                file: frame
+                    .location
+                    .as_ref()
+                    .and_then(|location| location.file.map(str::to_string)),
+                line: frame.location.as_ref().and_then(|location| location.line),
+            });
+        }
+    }
+    if frames.is_empty() {
+        frames.push(BacktraceFrame {
+            pc,
+            function: symbols
+                .map(|symbols| symbols.function_name(pc))
+                .unwrap_or_else(|| format!("function_{:#x}", pc)),
+            file: None,
+            line: None,
+        });
+    }
+    frames
+}
+
+// Capture the backtrace of a failed execution, given the instruction traces
+// of the program's executions, in the order they finished, and the logs.
+//
+// A failure unwinds every invocation at once, so the first execution which
+// didn't exit normally is the one which faulted, or invoked the program
+// which did.
+pub(crate) fn capture(elf: &[u8], traces: &[Vec<[u64; 12]>], logs: &[String]) -> Option<Backtrace> {
+    let panic_location = panic_location(logs);
+
+    let file = File::parse(elf).ok()?;
+    let text_section = file.section_by_name(".text")?;
+    let text = text_section.data().ok()?;
+    let context = Context::new(&file).ok();
+    let symbols = ElfSymbols::parse(elf).ok();
+
+    let stack = traces
+        .iter()
+        .find_map(|trace| fault_stack(trace, text))
+        .unwrap_or_default();
+    if stack.is_empty() && panic_location.is_none() {
+        return None;
+    }
+    let frames = stack
+        .into_iter()
+        .flat_map(|pc| {
+            symbolize(
+                pc,
+                text_section.address(),
+                context.as_ref(),
+                symbols.as_ref(),
+            )
+        })
+        .collect();
+
+    Some(Backtrace {
+        panic_location,
+        frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::trace::{FRAME_POINTER_INDEX, PC_INDEX},
+    };
+
+    fn trace_entry(frame_pointer: u64, pc: u64) -> [u64; 12] {
+        let mut state = [0; 12];
+        state[FRAME_POINTER_INDEX] = frame_pointer;
+        state[PC_INDEX] = pc;
+        state
+    }
+
+    #[test]
+    fn test_panic_location() {
+        let logs = vec![
+            String::from("Program log: panicked at src/lib.rs:12:5"),
+            String::from(
+                "Program 11111111111111111111111111111111 failed: \
+                 SBF program Panicked in src/lib.rs at 12:5",
+            ),
+        ];
+        assert_eq!(
+            panic_location(&logs),
+            Some(PanicLocation {
+                file: String::from("src/lib.rs"),
+                line: 12,
+                column: 5,
+            })
+        );
+        assert_eq!(panic_location(&logs[..1]), None);
+    }
+
+    #[test]
+    fn test_fault_stack() {
+        let mut text = vec![0; 4 * INSN_SIZE as usize];
+        text[3 * INSN_SIZE as usize] = EXIT_OPCODE;
+
+        // The entrypoint calls a function, which returns, then exits.
+        let trace = [
+            trace_entry(100, 0),
+            trace_entry(200, 1),
+            trace_entry(200, 3),
+            trace_entry(100, 3),
+        ];
+        assert_eq!(fault_stack(&trace, &text), None);
+
+        // The entrypoint calls a function, which faults.
+        assert_eq!(fault_stack(&trace[..2], &text), Some(vec![1, 0]));
+
+        // The function returns, then the entrypoint faults.
+        let trace = [
+            trace_entry(100, 0),
+            trace_entry(200, 3),
+            trace_entry(100, 2),
+        ];
+        assert_eq!(fault_stack(&trace, &text), Some(vec![2]));
+
+        assert_eq!(fault_stack(&[], &text), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let backtrace = Backtrace {
+            panic_location: Some(PanicLocation {
+                file: String::from("src/lib.rs"),
+                line: 12,
+                column: 5,
+            }),
+            frames: vec![
+                BacktraceFrame {
+                    pc: 0x1a,
+                    function: String::from("program::process"),
+                    file: Some(String::from("src/lib.rs")),
+                    line: Some(12),
+                },
+                BacktraceFrame {
+                    pc: 0x2,
+                    function: String::from("entrypoint"),
+                    file: None,
+                    line: None,
+                },
+            ],
+        };
+        assert_eq!(
+            backtrace.to_string(),
+            "panicked at src/lib.rs:12:5\n\
+             backtrace:\n   \
+             0: 0x1a program::process at src/lib.rs:12\n   \
+             1: 0x2 entrypoint\n"
+        );
+    }
+}
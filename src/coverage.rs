@@ -0,0 +1,229 @@
+//! SBF program code coverage, collected from the VM's instruction trace and
+//! mapped to source lines through the ELF's DWARF line info.
+
+use {
+    crate::trace::{INSN_SIZE, PC_INDEX},
+    addr2line::{
+        object::{File, Object, ObjectSection},
+        Context,
+    },
+    std::{collections::BTreeMap, io::Write},
+    thiserror::Error,
+};
+
+/// Errors possible for reporting coverage.
+#[derive(Debug, Error)]
+pub enum CoverageError {
+    /// The ELF could not be parsed.
+    #[error("Invalid ELF: {0}")]
+    InvalidElf(String),
+    /// The ELF has no `.text` section.
+    #[error("Missing text section")]
+    MissingTextSection,
+    /// The ELF's DWARF debug info could not be parsed.
+    #[error("Invalid debug info: {0}")]
+    InvalidDebugInfo(String),
+    /// The report could not be written.
+    #[error("Failed to write report: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The number of times each instruction of an SBF program was executed, and
+/// each edge between instructions was taken.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    /// Execution counts, keyed by program counter, the instruction's index
+    /// in the text section.
+    pub program_counters: BTreeMap<u64, u64>,
+    /// Execution counts of each edge between consecutively executed
+    /// instructions, keyed by the program counters of both.
+    pub edges: BTreeMap<(u64, u64), u64>,
+}
+
+impl Coverage {
+    /// Record a VM instruction trace of one program execution.
+    pub fn record_trace(&mut self, trace: &[[u64; 12]]) {
+        for state in trace {
+            *self.program_counters.entry(state[PC_INDEX]).or_default() += 1;
+        }
+        for window in trace.windows(2) {
+            let edge = (window[0][PC_INDEX], window[1][PC_INDEX]);
+            *self.edges.entry(edge).or_default() += 1;
+        }
+    }
+
+    /// Merge the execution counts of another coverage into this one.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (pc, count) in &other.program_counters {
+            *self.program_counters.entry(*pc).or_default() += count;
+        }
+        for (edge, count) in &other.edges {
+            *self.edges.entry(*edge).or_default() += count;
+        }
+    }
+
+    /// Add the edge execution counts to 8-bit counters, such as libFuzzer's
+    /// extra counters. Each edge is hashed to a counter, and counters
+    /// saturate rather than wrap.
+    pub fn update_edge_counters(&self, counters: &mut [u8]) {
+        if counters.is_empty() {
+            return;
+        }
+        for ((from, to), count) in &self.edges {
+            let index = edge_hash(*from, *to) % counters.len() as u64;
+            let counter = &mut counters[index as usize];
+            *counter = counter.saturating_add(u8::try_from(*count).unwrap_or(u8::MAX));
+        }
+    }
+
+    /// Map the execution counts to source lines, using the DWARF line info
+    /// of the program's ELF, which must be built with debug info.
+    ///
+    /// Every line with instructions is reported, including those never
+    /// executed.
+    pub fn line_coverage(&self, elf: &[u8]) -> Result<LineCoverage, CoverageError> {
+        let file =
+            File::parse(elf).map_err(|error| CoverageError::InvalidElf(error.to_string()))?;
+        let text_section = file
+            .section_by_name(".text")
+            .ok_or(CoverageError::MissingTextSection)?;
+        let context = Context::new(&file)
+            .map_err(|error| CoverageError::InvalidDebugInfo(error.to_string()))?;
+
+        let mut line_coverage = LineCoverage::default();
+        for pc in 0..text_section.size() / INSN_SIZE {
+            let address = text_section.address() + pc * INSN_SIZE;
+            let location = context
+                .find_location(address)
+                .map_err(|error| CoverageError::InvalidDebugInfo(error.to_string()))?;
+            if let Some((file, line)) =
+                location.and_then(|location| location.file.zip(location.line))
+            {
+                *line_coverage
+                    .files
+                    .entry(file.to_string())
+                    .or_default()
+                    .entry(line)
+                    .or_default() += self.program_counters.get(&pc).copied().unwrap_or(0);
+            }
+        }
+        Ok(line_coverage)
+    }
+
+    /// Write an lcov report of the coverage, for tools such as `genhtml`.
+    pub fn write_lcov(&self, elf: &[u8], writer: impl Write) -> Result<(), CoverageError> {
+        self.line_coverage(elf)?.write_lcov(writer)
+    }
+}
+
+// A stable hash of an edge, so counters are consistent across runs.
+fn edge_hash(from: u64, to: u64) -> u64 {
+    const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+    (from.wrapping_mul(MULTIPLIER) ^ to).wrapping_mul(MULTIPLIER) >> 16
+}
+
+/// The number of instructions executed on each source line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineCoverage {
+    /// Execution counts by line, keyed by source file path.
+    pub files: BTreeMap<String, BTreeMap<u32, u64>>,
+}
+
+impl LineCoverage {
+    /// Write an lcov report of the coverage, for tools such as `genhtml`.
+    pub fn write_lcov(&self, mut writer: impl Write) -> Result<(), CoverageError> {
+        for (file, lines) in &self.files {
+            writeln!(writer, "SF:{}", file)?;
+            for (line, count) in lines {
+                writeln!(writer, "DA:{},{}", line, count)?;
+            }
+            writeln!(writer, "LF:{}", lines.len())?;
+            writeln!(
+                writer,
+                "LH:{}",
+                lines.values().filter(|count| **count > 0).count()
+            )?;
+            writeln!(writer, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace_entry(pc: u64) -> [u64; 12] {
+        let mut state = [0; 12];
+        state[PC_INDEX] = pc;
+        state
+    }
+
+    #[test]
+    fn test_record_trace() {
+        let mut coverage = Coverage::default();
+        coverage.record_trace(&[trace_entry(0), trace_entry(1), trace_entry(0)]);
+
+        let mut other = Coverage::default();
+        other.record_trace(&[trace_entry(1), trace_entry(5)]);
+        coverage.merge(&other);
+
+        assert_eq!(
+            coverage.program_counters,
+            BTreeMap::from([(0, 2), (1, 2), (5, 1)])
+        );
+        assert_eq!(
+            coverage.edges,
+            BTreeMap::from([((0, 1), 1), ((1, 0), 1), ((1, 5), 1)])
+        );
+    }
+
+    #[test]
+    fn test_update_edge_counters() {
+        let mut coverage = Coverage::default();
+        coverage.record_trace(&[trace_entry(0), trace_entry(1)]);
+        coverage.edges.insert((4, 2), 300);
+
+        let mut counters = [0u8; 64];
+        coverage.update_edge_counters(&mut counters);
+        let index = |from, to| (edge_hash(from, to) % 64) as usize;
+        assert!(counters[index(0, 1)] >= 1);
+        assert_eq!(counters[index(4, 2)], u8::MAX);
+
+        // Counters accumulate and saturate.
+        coverage.update_edge_counters(&mut counters);
+        assert_eq!(counters[index(4, 2)], u8::MAX);
+
+        // No counters to update.
+        coverage.update_edge_counters(&mut []);
+    }
+
+    #[test]
+    fn test_write_lcov() {
+        let line_coverage = LineCoverage {
+            files: BTreeMap::from([
+                (
+                    String::from("src/lib.rs"),
+                    BTreeMap::from([(3, 2), (4, 0), (7, 1)]),
+                ),
+                (String::from("src/processor.rs"), BTreeMap::from([(10, 0)])),
+            ]),
+        };
+
+        let mut report = Vec::new();
+        line_coverage.write_lcov(&mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "SF:src/lib.rs\nDA:3,2\nDA:4,0\nDA:7,1\nLF:3\nLH:2\nend_of_record\n\
+             SF:src/processor.rs\nDA:10,0\nLF:1\nLH:0\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_line_coverage_invalid_elf() {
+        assert!(matches!(
+            Coverage::default().line_coverage(&[1, 2, 3, 4]),
+            Err(CoverageError::InvalidElf(_))
+        ));
+    }
+}
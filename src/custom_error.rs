@@ -0,0 +1,424 @@
+//! Decoding of program-specific custom error codes, for readable reports.
+
+use {
+    num_traits::FromPrimitive,
+    std::{
+        fmt::{Debug, Display},
+        marker::PhantomData,
+    },
+};
+
+/// The offset of program-specific error codes in Anchor programs.
+pub const ANCHOR_ERROR_CODE_OFFSET: u32 = 6000;
+
+/// A decoded custom error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedCustomError {
+    /// The name of the error, such as
+    /// `MyProgramError::InsufficientCollateral`.
+    pub name: String,
+    /// The error message.
+    pub message: String,
+}
+
+/// Maps the custom error codes of a program to error names and messages.
+pub trait CustomErrorDecoder {
+    /// Decode a custom error code, if it is known to the decoder.
+    fn decode(&self, code: u32) -> Option<DecodedCustomError>;
+}
+
+impl<F: Fn(u32) -> Option<DecodedCustomError>> CustomErrorDecoder for F {
+    fn decode(&self, code: u32) -> Option<DecodedCustomError> {
+        self(code)
+    }
+}
+
+/// Decodes with the first decoder, falling back to the second.
+impl<A: CustomErrorDecoder, B: CustomErrorDecoder> CustomErrorDecoder for (A, B) {
+    fn decode(&self, code: u32) -> Option<DecodedCustomError> {
+        self.0.decode(code).or_else(|| self.1.decode(code))
+    }
+}
+
+/// Decodes custom error codes into a program's error enum, such as one
+/// deriving `num_derive::FromPrimitive` and `thiserror::Error`.
+pub struct EnumErrorDecoder<E> {
+    offset: u32,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EnumErrorDecoder<E> {
+    fn default() -> Self {
+        Self::with_offset(0)
+    }
+}
+
+impl<E> EnumErrorDecoder<E> {
+    /// Create a decoder for error codes starting at `offset`, such as
+    /// `ANCHOR_ERROR_CODE_OFFSET` for Anchor programs.
+    pub fn with_offset(offset: u32) -> Self {
+        Self {
+            offset,
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<E: FromPrimitive + Debug + Display> CustomErrorDecoder for EnumErrorDecoder<E> {
+    fn decode(&self, code: u32) -> Option<DecodedCustomError> {
+        let error = E::from_u32(code.checked_sub(self.offset)?)?;
+        let type_name = std::any::type_name::<E>();
+        let type_name = type_name.rsplit("::").next().unwrap_or(type_name);
+        Some(DecodedCustomError {
+            name: format!("{}::{:?}", type_name, error),
+            message: error.to_string(),
+        })
+    }
+}
+
+// Anchor framework error codes, as of `anchor-lang` `0.29.0`.
+static ANCHOR_ERRORS: &[(u32, &str, &str)] = &[
+    (
+        100,
+        "InstructionMissing",
+        "8 byte instruction identifier not provided",
+    ),
+    (
+        101,
+        "InstructionFallbackNotFound",
+        "Fallback functions are not supported",
+    ),
+    (
+        102,
+        "InstructionDidNotDeserialize",
+        "The program could not deserialize the given instruction",
+    ),
+    (
+        103,
+        "InstructionDidNotSerialize",
+        "The program could not serialize the given instruction",
+    ),
+    (
+        1000,
+        "IdlInstructionStub",
+        "The program was compiled without idl instructions",
+    ),
+    (
+        1001,
+        "IdlInstructionInvalidProgram",
+        "Invalid program given to the IDL instruction",
+    ),
+    (
+        1002,
+        "IdlAccountNotEmpty",
+        "IDL account must be empty in order to resize, try closing first",
+    ),
+    (
+        1500,
+        "EventInstructionStub",
+        "The program was compiled without `event-cpi` feature",
+    ),
+    (2000, "ConstraintMut", "A mut constraint was violated"),
+    (
+        2001,
+        "ConstraintHasOne",
+        "A has one constraint was violated",
+    ),
+    (2002, "ConstraintSigner", "A signer constraint was violated"),
+    (2003, "ConstraintRaw", "A raw constraint was violated"),
+    (2004, "ConstraintOwner", "An owner constraint was violated"),
+    (
+        2005,
+        "ConstraintRentExempt",
+        "A rent exemption constraint was violated",
+    ),
+    (2006, "ConstraintSeeds", "A seeds constraint was violated"),
+    (
+        2007,
+        "ConstraintExecutable",
+        "An executable constraint was violated",
+    ),
+    (
+        2008,
+        "ConstraintState",
+        "Deprecated Error, feel free to replace with something else",
+    ),
+    (
+        2009,
+        "ConstraintAssociated",
+        "An associated constraint was violated",
+    ),
+    (
+        2010,
+        "ConstraintAssociatedInit",
+        "An associated init constraint was violated",
+    ),
+    (2011, "ConstraintClose", "A close constraint was violated"),
+    (
+        2012,
+        "ConstraintAddress",
+        "An address constraint was violated",
+    ),
+    (2013, "ConstraintZero", "Expected zero account discriminant"),
+    (
+        2014,
+        "ConstraintTokenMint",
+        "A token mint constraint was violated",
+    ),
+    (
+        2015,
+        "ConstraintTokenOwner",
+        "A token owner constraint was violated",
+    ),
+    (
+        2016,
+        "ConstraintMintMintAuthority",
+        "A mint mint authority constraint was violated",
+    ),
+    (
+        2017,
+        "ConstraintMintFreezeAuthority",
+        "A mint freeze authority constraint was violated",
+    ),
+    (
+        2018,
+        "ConstraintMintDecimals",
+        "A mint decimals constraint was violated",
+    ),
+    (2019, "ConstraintSpace", "A space constraint was violated"),
+    (
+        2020,
+        "ConstraintAccountIsNone",
+        "A required account for the constraint is None",
+    ),
+    (
+        2021,
+        "ConstraintTokenTokenProgram",
+        "A token account token program constraint was violated",
+    ),
+    (
+        2022,
+        "ConstraintMintTokenProgram",
+        "A mint token program constraint was violated",
+    ),
+    (
+        2023,
+        "ConstraintAssociatedTokenTokenProgram",
+        "An associated token account token program constraint was violated",
+    ),
+    (2500, "RequireViolated", "A require expression was violated"),
+    (
+        2501,
+        "RequireEqViolated",
+        "A require_eq expression was violated",
+    ),
+    (
+        2502,
+        "RequireKeysEqViolated",
+        "A require_keys_eq expression was violated",
+    ),
+    (
+        2503,
+        "RequireNeqViolated",
+        "A require_neq expression was violated",
+    ),
+    (
+        2504,
+        "RequireKeysNeqViolated",
+        "A require_keys_neq expression was violated",
+    ),
+    (
+        2505,
+        "RequireGtViolated",
+        "A require_gt expression was violated",
+    ),
+    (
+        2506,
+        "RequireGteViolated",
+        "A require_gte expression was violated",
+    ),
+    (
+        3000,
+        "AccountDiscriminatorAlreadySet",
+        "The account discriminator was already set on this account",
+    ),
+    (
+        3001,
+        "AccountDiscriminatorNotFound",
+        "No 8 byte discriminator was found on the account",
+    ),
+    (
+        3002,
+        "AccountDiscriminatorMismatch",
+        "8 byte discriminator did not match what was expected",
+    ),
+    (
+        3003,
+        "AccountDidNotDeserialize",
+        "Failed to deserialize the account",
+    ),
+    (
+        3004,
+        "AccountDidNotSerialize",
+        "Failed to serialize the account",
+    ),
+    (
+        3005,
+        "AccountNotEnoughKeys",
+        "Not enough account keys given to the instruction",
+    ),
+    (
+        3006,
+        "AccountNotMutable",
+        "The given account is not mutable",
+    ),
+    (
+        3007,
+        "AccountOwnedByWrongProgram",
+        "The given account is owned by a different program than expected",
+    ),
+    (3008, "InvalidProgramId", "Program ID was not as expected"),
+    (
+        3009,
+        "InvalidProgramExecutable",
+        "Program account is not executable",
+    ),
+    (3010, "AccountNotSigner", "The given account did not sign"),
+    (
+        3011,
+        "AccountNotSystemOwned",
+        "The given account is not owned by the system program",
+    ),
+    (
+        3012,
+        "AccountNotInitialized",
+        "The program expected this account to be already initialized",
+    ),
+    (
+        3013,
+        "AccountNotProgramData",
+        "The given account is not a program data account",
+    ),
+    (
+        3014,
+        "AccountNotAssociatedTokenAccount",
+        "The given account is not the associated token account",
+    ),
+    (
+        3015,
+        "AccountSysvarMismatch",
+        "The given public key does not match the required sysvar",
+    ),
+    (
+        3016,
+        "AccountReallocExceedsLimit",
+        "The account reallocation exceeds the MAX_PERMITTED_DATA_INCREASE limit",
+    ),
+    (
+        3017,
+        "AccountDuplicateReallocs",
+        "The account was duplicated for more than one reallocation",
+    ),
+    (
+        4100,
+        "DeclaredProgramIdMismatch",
+        "The declared program id does not match the actual program id",
+    ),
+    (
+        4101,
+        "TryingToInitPayerAsProgramAccount",
+        "You cannot/should not initialize the payer account as a program account",
+    ),
+    (
+        5000,
+        "Deprecated",
+        "The API being used is deprecated and should no longer be used",
+    ),
+];
+
+/// Decodes the error codes of the Anchor framework itself. Chain it with a
+/// decoder for the program's own errors, which start at
+/// `ANCHOR_ERROR_CODE_OFFSET`.
+#[derive(Default)]
+pub struct AnchorErrorDecoder;
+
+impl CustomErrorDecoder for AnchorErrorDecoder {
+    fn decode(&self, code: u32) -> Option<DecodedCustomError> {
+        ANCHOR_ERRORS
+            .iter()
+            .find(|(anchor_code, _, _)| *anchor_code == code)
+            .map(|(_, name, message)| DecodedCustomError {
+                name: format!("anchor_lang::ErrorCode::{}", name),
+                message: message.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, num_derive::FromPrimitive, thiserror::Error};
+
+    #[derive(Debug, Error, FromPrimitive)]
+    enum TestError {
+        #[error("Not enough collateral")]
+        InsufficientCollateral,
+        #[error("The vault is frozen")]
+        VaultFrozen,
+    }
+
+    #[test]
+    fn test_enum_error_decoder() {
+        let decoder = EnumErrorDecoder::<TestError>::default();
+        assert_eq!(
+            decoder.decode(1),
+            Some(DecodedCustomError {
+                name: String::from("TestError::VaultFrozen"),
+                message: String::from("The vault is frozen"),
+            })
+        );
+        assert_eq!(decoder.decode(2), None);
+
+        let decoder = EnumErrorDecoder::<TestError>::with_offset(ANCHOR_ERROR_CODE_OFFSET);
+        assert_eq!(
+            decoder.decode(6000),
+            Some(DecodedCustomError {
+                name: String::from("TestError::InsufficientCollateral"),
+                message: String::from("Not enough collateral"),
+            })
+        );
+        assert_eq!(decoder.decode(0), None);
+    }
+
+    #[test]
+    fn test_anchor_error_decoder() {
+        let decoder = (
+            AnchorErrorDecoder,
+            EnumErrorDecoder::<TestError>::with_offset(ANCHOR_ERROR_CODE_OFFSET),
+        );
+        assert_eq!(
+            decoder.decode(2000),
+            Some(DecodedCustomError {
+                name: String::from("anchor_lang::ErrorCode::ConstraintMut"),
+                message: String::from("A mut constraint was violated"),
+            })
+        );
+        assert_eq!(
+            decoder.decode(3012).unwrap().name,
+            "anchor_lang::ErrorCode::AccountNotInitialized"
+        );
+        assert_eq!(decoder.decode(6001).unwrap().name, "TestError::VaultFrozen");
+        assert_eq!(decoder.decode(42), None);
+    }
+
+    #[test]
+    fn test_closure_decoder() {
+        let decoder = |code| {
+            (code == 42).then(|| DecodedCustomError {
+                name: String::from("Answer"),
+                message: String::from("The answer"),
+            })
+        };
+        assert_eq!(decoder.decode(42).unwrap().name, "Answer");
+        assert_eq!(decoder.decode(41), None);
+    }
+}
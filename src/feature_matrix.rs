@@ -0,0 +1,250 @@
+//! Feature-gate matrix execution, for detecting program behavior that depends
+//! on runtime features.
+
+use {
+    crate::{
+        execute_instruction,
+        fixture::{context::FixtureContext, error::FixtureError, feature_set::supported_features},
+        result::ExecutionResult,
+    },
+    solana_sdk::{feature_set::FeatureSet, pubkey::Pubkey},
+};
+
+/// The maximum number of features for which every combination can be
+/// executed.
+pub const MAX_COMBINATION_FEATURES: usize = 8;
+
+/// How feature set variants are derived from a fixture's feature set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeatureMatrixMode {
+    /// Toggle each feature individually.
+    Toggle,
+    /// Execute every combination of the features.
+    Combinations,
+}
+
+/// A feature set variant, relative to a fixture's feature set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeatureSetVariant {
+    /// Features activated relative to the fixture's feature set.
+    pub activated: Vec<Pubkey>,
+    /// Features deactivated relative to the fixture's feature set.
+    pub deactivated: Vec<Pubkey>,
+}
+
+impl FeatureSetVariant {
+    fn apply(&self, feature_set: &mut FeatureSet) {
+        self.activated
+            .iter()
+            .for_each(|id| feature_set.activate(id, 0));
+        self.deactivated
+            .iter()
+            .for_each(|id| feature_set.deactivate(id));
+    }
+}
+
+/// A feature set variant whose outcome differs from the fixture's.
+#[derive(Debug)]
+pub struct DivergentVariant {
+    /// The feature set variant.
+    pub variant: FeatureSetVariant,
+    /// The result of executing the fixture with the variant.
+    pub result: ExecutionResult,
+}
+
+/// The report of a feature matrix execution.
+#[derive(Debug)]
+pub struct FeatureMatrixReport {
+    /// The result of executing the fixture with its own feature set.
+    pub baseline: ExecutionResult,
+    /// The number of variants executed, excluding the baseline.
+    pub variants_executed: usize,
+    /// The variants whose outcome differs from the baseline.
+    pub divergent_variants: Vec<DivergentVariant>,
+}
+
+fn feature_set_variants(
+    feature_set: &FeatureSet,
+    features: &[Pubkey],
+    mode: FeatureMatrixMode,
+) -> Result<Vec<FeatureSetVariant>, FixtureError> {
+    let mut unique_features: Vec<Pubkey> = Vec::with_capacity(features.len());
+    for feature in features {
+        if !supported_features().contains(feature) {
+            return Err(FixtureError::UnsupportedFeature);
+        }
+        if !unique_features.contains(feature) {
+            unique_features.push(*feature);
+        }
+    }
+
+    match mode {
+        FeatureMatrixMode::Toggle => Ok(unique_features
+            .into_iter()
+            .map(|id| {
+                if feature_set.is_active(&id) {
+                    FeatureSetVariant {
+                        deactivated: vec![id],
+                        ..FeatureSetVariant::default()
+                    }
+                } else {
+                    FeatureSetVariant {
+                        activated: vec![id],
+                        ..FeatureSetVariant::default()
+                    }
+                }
+            })
+            .collect()),
+        FeatureMatrixMode::Combinations => {
+            if unique_features.len() > MAX_COMBINATION_FEATURES {
+                return Err(FixtureError::TooManyFeatures);
+            }
+            Ok((0..1usize << unique_features.len())
+                .map(|mask| {
+                    let mut variant = FeatureSetVariant::default();
+                    for (i, id) in unique_features.iter().enumerate() {
+                        let active = mask & (1 << i) != 0;
+                        if active && !feature_set.is_active(id) {
+                            variant.activated.push(*id);
+                        } else if !active && feature_set.is_active(id) {
+                            variant.deactivated.push(*id);
+                        }
+                    }
+                    variant
+                })
+                // Skip the variant matching the fixture's feature set.
+                .filter(|variant| !variant.activated.is_empty() || !variant.deactivated.is_empty())
+                .collect())
+        }
+    }
+}
+
+/// Execute a fixture's instruction with its own feature set, then once for
+/// each feature set variant derived from the provided features, and report
+/// the variants which change the outcome.
+///
+/// All features must be supported by the harness.
+pub fn run_feature_matrix(
+    context: &FixtureContext,
+    features: &[Pubkey],
+    mode: FeatureMatrixMode,
+    elf: &[u8],
+) -> Result<FeatureMatrixReport, FixtureError> {
+    let variants = feature_set_variants(&context.feature_set, features, mode)?;
+
+    let baseline = execute_instruction(context.clone(), elf);
+
+    let variants_executed = variants.len();
+    let divergent_variants = variants
+        .into_iter()
+        .filter_map(|variant| {
+            let mut context = context.clone();
+            variant.apply(&mut context.feature_set);
+            let result = execute_instruction(context, elf);
+            (!result.same_outcome(&baseline)).then_some(DivergentVariant { variant, result })
+        })
+        .collect();
+
+    Ok(FeatureMatrixReport {
+        baseline,
+        variants_executed,
+        divergent_variants,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_set_variants_toggle() {
+        let active = supported_features()[0];
+        let inactive = supported_features()[1];
+
+        let mut feature_set = FeatureSet::default();
+        feature_set.activate(&active, 0);
+
+        // Duplicates are ignored.
+        let variants = feature_set_variants(
+            &feature_set,
+            &[active, inactive, active],
+            FeatureMatrixMode::Toggle,
+        )
+        .unwrap();
+        assert_eq!(
+            variants,
+            vec![
+                FeatureSetVariant {
+                    activated: vec![],
+                    deactivated: vec![active],
+                },
+                FeatureSetVariant {
+                    activated: vec![inactive],
+                    deactivated: vec![],
+                },
+            ]
+        );
+
+        let mut toggled = feature_set.clone();
+        variants[0].apply(&mut toggled);
+        assert!(!toggled.is_active(&active));
+        assert!(!toggled.is_active(&inactive));
+    }
+
+    #[test]
+    fn test_feature_set_variants_combinations() {
+        let active = supported_features()[0];
+        let inactive = supported_features()[1];
+
+        let mut feature_set = FeatureSet::default();
+        feature_set.activate(&active, 0);
+
+        let variants = feature_set_variants(
+            &feature_set,
+            &[active, inactive],
+            FeatureMatrixMode::Combinations,
+        )
+        .unwrap();
+        assert_eq!(
+            variants,
+            vec![
+                FeatureSetVariant {
+                    activated: vec![],
+                    deactivated: vec![active],
+                },
+                FeatureSetVariant {
+                    activated: vec![inactive],
+                    deactivated: vec![active],
+                },
+                FeatureSetVariant {
+                    activated: vec![inactive],
+                    deactivated: vec![],
+                },
+            ]
+        );
+
+        // Too many features
+        assert_eq!(
+            feature_set_variants(
+                &feature_set,
+                &supported_features()[..MAX_COMBINATION_FEATURES + 1],
+                FeatureMatrixMode::Combinations,
+            )
+            .unwrap_err(),
+            FixtureError::TooManyFeatures
+        );
+    }
+
+    #[test]
+    fn test_feature_set_variants_unsupported_feature() {
+        assert_eq!(
+            feature_set_variants(
+                &FeatureSet::default(),
+                &[Pubkey::new_unique()],
+                FeatureMatrixMode::Toggle,
+            )
+            .unwrap_err(),
+            FixtureError::UnsupportedFeature
+        );
+    }
+}
@@ -4,7 +4,7 @@
 use {
     super::{error::FixtureError, proto},
     solana_sdk::{
-        account::{Account, AccountSharedData},
+        account::{Account, AccountSharedData, ReadableAccount},
         pubkey::Pubkey,
     },
 };
@@ -46,6 +46,19 @@ impl TryFrom<proto::AcctState> for (Pubkey, AccountSharedData) {
     }
 }
 
+impl From<&(Pubkey, AccountSharedData)> for proto::AcctState {
+    fn from((pubkey, account): &(Pubkey, AccountSharedData)) -> Self {
+        Self {
+            address: pubkey.to_bytes().to_vec(),
+            owner: account.owner().to_bytes().to_vec(),
+            lamports: account.lamports(),
+            data: account.data().to_vec(),
+            executable: account.executable(),
+            rent_epoch: account.rent_epoch(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
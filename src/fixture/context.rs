@@ -9,8 +9,39 @@ use {
     },
 };
 
+/// The deployment status of the program being invoked.
+///
+/// Only loader v4 tracks retracted and finalized programs, so other loaders
+/// treat them as deployed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ProgramStatus {
+    /// The program is deployed and can be upgraded.
+    #[default]
+    Deployed,
+    /// The program has been retracted and can't be invoked.
+    Retracted,
+    /// The program is deployed and can't be upgraded.
+    Finalized,
+    /// The program has been closed and can't be invoked.
+    Closed,
+}
+
+impl TryFrom<i32> for ProgramStatus {
+    type Error = FixtureError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match proto::ProgramStatus::from_i32(value) {
+            Some(proto::ProgramStatus::Deployed) => Ok(Self::Deployed),
+            Some(proto::ProgramStatus::Retracted) => Ok(Self::Retracted),
+            Some(proto::ProgramStatus::Finalized) => Ok(Self::Finalized),
+            Some(proto::ProgramStatus::Closed) => Ok(Self::Closed),
+            None => Err(FixtureError::InvalidProgramStatus),
+        }
+    }
+}
+
 /// Instruction context fixture.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FixtureContext {
     /// The program ID of the program being invoked.
     pub program_id: Pubkey,
@@ -26,6 +57,15 @@ pub struct FixtureContext {
     pub instruction_accounts: Vec<AccountMeta>,
     /// The instruction data.
     pub instruction_data: Vec<u8>,
+    /// The deployment status of the program.
+    pub program_status: ProgramStatus,
+    /// The upgrade authority of the program, or `None` if the program is
+    /// immutable.
+    pub upgrade_authority: Option<Pubkey>,
+    /// The slot in which the program was deployed, or `None` if it was
+    /// deployed before the current slot. A program deployed in the current
+    /// slot is not visible until the next one.
+    pub deployment_slot: Option<u64>,
 }
 
 impl TryFrom<proto::InstrContext> for FixtureContext {
@@ -40,6 +80,9 @@ impl TryFrom<proto::InstrContext> for FixtureContext {
             accounts,
             instr_accounts,
             data: instruction_data,
+            program_status,
+            upgrade_authority,
+            deployment_slot,
         } = input;
 
         let program_id = Pubkey::new_from_array(
@@ -85,6 +128,18 @@ impl TryFrom<proto::InstrContext> for FixtureContext {
             )
             .collect::<Result<Vec<_>, _>>()?;
 
+        let program_status = program_status.try_into()?;
+
+        let upgrade_authority = if upgrade_authority.is_empty() {
+            None
+        } else {
+            Some(Pubkey::new_from_array(
+                upgrade_authority
+                    .try_into()
+                    .map_err(|_| FixtureError::InvalidPubkeyBytes)?,
+            ))
+        };
+
         Ok(Self {
             program_id,
             loader_id,
@@ -93,6 +148,9 @@ impl TryFrom<proto::InstrContext> for FixtureContext {
             accounts,
             instruction_accounts,
             instruction_data,
+            program_status,
+            upgrade_authority,
+            deployment_slot,
         })
     }
 }
@@ -191,6 +249,28 @@ mod tests {
         assert_eq!(context.loader_id, loader_id);
         assert_eq!(context.accounts, accounts);
         assert_eq!(context.instruction_accounts, instruction_accounts);
+        assert_eq!(context.program_status, ProgramStatus::Deployed);
+        assert_eq!(context.upgrade_authority, None);
+        assert_eq!(context.deployment_slot, None);
+
+        // Program status
+        let context = FixtureContext::try_from(proto::InstrContext {
+            program_status: proto::ProgramStatus::Finalized as i32,
+            ..input.clone()
+        })
+        .unwrap();
+        assert_eq!(context.program_status, ProgramStatus::Finalized);
+
+        // Upgrade authority and deployment slot
+        let upgrade_authority = Pubkey::new_unique();
+        let context = FixtureContext::try_from(proto::InstrContext {
+            upgrade_authority: upgrade_authority.to_bytes().to_vec(),
+            deployment_slot: Some(42),
+            ..input.clone()
+        })
+        .unwrap();
+        assert_eq!(context.upgrade_authority, Some(upgrade_authority));
+        assert_eq!(context.deployment_slot, Some(42));
 
         // Failures
         let too_many_bytes = vec![0; 33];
@@ -249,5 +329,25 @@ mod tests {
             .unwrap_err(),
             FixtureError::AccountMissing
         );
+
+        // Invalid program status
+        assert_eq!(
+            FixtureContext::try_from(proto::InstrContext {
+                program_status: 4,
+                ..input.clone()
+            })
+            .unwrap_err(),
+            FixtureError::InvalidProgramStatus
+        );
+
+        // Too few bytes for upgrade_authority
+        assert_eq!(
+            FixtureContext::try_from(proto::InstrContext {
+                upgrade_authority: too_few_bytes.clone(),
+                ..input.clone()
+            })
+            .unwrap_err(),
+            FixtureError::InvalidPubkeyBytes
+        );
     }
 }
@@ -1,18 +1,322 @@
 //! Effects of a single instruction.
 
 use {
-    super::{error::FixtureError, proto},
-    solana_sdk::{account::AccountSharedData, pubkey::Pubkey},
+    super::{error::FixtureError, instr_error, proto},
+    crate::result::{ExecutionResult, InnerInstruction, ModifiedProgram, ModifiedProgramStatus},
+    solana_sdk::{
+        account::AccountSharedData,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        transaction_context::TransactionReturnData,
+    },
 };
 
 /// Represents the effects of a single instruction.
+#[derive(Debug)]
 pub struct FixtureEffects {
     /// The result of the instruction.
-    pub result: i32,
-    /// The custom error of the instruction, if any.
-    pub custom_error: u64,
+    pub result: Result<(), InstructionError>,
     /// Resulting accounts with state, to be checked post-simulation.
     pub modified_accounts: Vec<(Pubkey, AccountSharedData)>,
+    /// The expected return data, or `None` if it is not checked. Empty data
+    /// means no data was returned.
+    pub return_data: Option<TransactionReturnData>,
+    /// The expected inner instructions, or `None` if they are not checked.
+    pub inner_instructions: Option<ExpectedInnerInstructions>,
+    /// The expected programs deployed, upgraded or closed by the
+    /// instruction, in any order, or `None` if they are not checked.
+    pub modified_programs: Option<Vec<ModifiedProgram>>,
+}
+
+impl FixtureEffects {
+    /// The first mismatch between these effects and an execution result, if
+    /// any. Modified accounts missing from the result are not checked.
+    pub fn mismatch(&self, result: &ExecutionResult) -> Option<EffectsMismatch> {
+        if !instr_error::results_match(&self.result, &result.result) {
+            return Some(EffectsMismatch::Result {
+                expected: self.result.clone(),
+                actual: result.result.clone(),
+            });
+        }
+        for (key, expected_modified_account) in &self.modified_accounts {
+            let account = result
+                .resulting_accounts
+                .iter()
+                .rev()
+                .find_map(|(pubkey, account)| (pubkey == key).then_some(account));
+            if let Some(account) = account.filter(|account| *account != expected_modified_account) {
+                return Some(EffectsMismatch::Account {
+                    pubkey: *key,
+                    expected: expected_modified_account.clone(),
+                    actual: account.clone(),
+                });
+            }
+        }
+        if let Some(expected_return_data) = &self.return_data {
+            // Empty return data means no data was returned.
+            let expected_return_data = Some(expected_return_data.clone())
+                .filter(|return_data| !return_data.data.is_empty());
+            if result.return_data != expected_return_data {
+                return Some(EffectsMismatch::ReturnData {
+                    expected: expected_return_data,
+                    actual: result.return_data.clone(),
+                });
+            }
+        }
+        if let Some(expected_inner_instructions) = &self.inner_instructions {
+            if !expected_inner_instructions.matches(&result.inner_instructions) {
+                return Some(EffectsMismatch::InnerInstructions {
+                    expected: expected_inner_instructions.clone(),
+                    actual: result.inner_instructions.clone(),
+                });
+            }
+        }
+        if let Some(expected_modified_programs) = &self.modified_programs {
+            if expected_modified_programs.len() != result.modified_programs.len()
+                || !expected_modified_programs
+                    .iter()
+                    .all(|program| result.modified_programs.contains(program))
+            {
+                return Some(EffectsMismatch::ModifiedPrograms {
+                    expected: expected_modified_programs.clone(),
+                    actual: result.modified_programs.clone(),
+                });
+            }
+        }
+        None
+    }
+}
+
+/// A mismatch between expected effects and an execution result.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EffectsMismatch {
+    /// The instruction result differs.
+    Result {
+        /// The expected result.
+        expected: Result<(), InstructionError>,
+        /// The actual result.
+        actual: Result<(), InstructionError>,
+    },
+    /// A modified account's state differs.
+    Account {
+        /// The account's public key.
+        pubkey: Pubkey,
+        /// The expected account state.
+        expected: AccountSharedData,
+        /// The actual account state.
+        actual: AccountSharedData,
+    },
+    /// The return data differs.
+    ReturnData {
+        /// The expected return data, or `None` for no data.
+        expected: Option<TransactionReturnData>,
+        /// The actual return data.
+        actual: Option<TransactionReturnData>,
+    },
+    /// The inner instructions don't meet the expectation.
+    InnerInstructions {
+        /// The expected inner instructions.
+        expected: ExpectedInnerInstructions,
+        /// The actual inner instructions.
+        actual: Vec<InnerInstruction>,
+    },
+    /// The modified programs differ.
+    ModifiedPrograms {
+        /// The expected modified programs.
+        expected: Vec<ModifiedProgram>,
+        /// The actual modified programs.
+        actual: Vec<ModifiedProgram>,
+    },
+}
+
+/// An expected sequence of inner instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpectedInnerInstructions {
+    /// The inner instructions must match exactly.
+    Exact(Vec<InnerInstruction>),
+    /// The inner instructions must begin with these instructions.
+    Prefix(Vec<InnerInstruction>),
+}
+
+impl ExpectedInnerInstructions {
+    /// Whether the given inner instructions meet the expectation.
+    pub fn matches(&self, inner_instructions: &[InnerInstruction]) -> bool {
+        match self {
+            Self::Exact(expected) => inner_instructions == expected.as_slice(),
+            Self::Prefix(expected) => inner_instructions.starts_with(expected),
+        }
+    }
+}
+
+fn to_pubkey(bytes: Vec<u8>) -> Result<Pubkey, FixtureError> {
+    Ok(Pubkey::new_from_array(
+        bytes
+            .try_into()
+            .map_err(|_| FixtureError::InvalidPubkeyBytes)?,
+    ))
+}
+
+impl TryFrom<proto::InnerInstr> for InnerInstruction {
+    type Error = FixtureError;
+
+    fn try_from(input: proto::InnerInstr) -> Result<Self, Self::Error> {
+        let proto::InnerInstr {
+            program_id,
+            accounts,
+            data,
+            stack_height,
+        } = input;
+
+        let accounts = accounts
+            .into_iter()
+            .map(
+                |proto::InnerInstrAcct {
+                     address,
+                     is_signer,
+                     is_writable,
+                 }| {
+                    Ok(AccountMeta {
+                        pubkey: to_pubkey(address)?,
+                        is_signer,
+                        is_writable,
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>, FixtureError>>()?;
+
+        Ok(Self {
+            instruction: Instruction {
+                program_id: to_pubkey(program_id)?,
+                accounts,
+                data,
+            },
+            stack_height,
+        })
+    }
+}
+
+impl From<&InnerInstruction> for proto::InnerInstr {
+    fn from(input: &InnerInstruction) -> Self {
+        let InnerInstruction {
+            instruction,
+            stack_height,
+        } = input;
+
+        Self {
+            program_id: instruction.program_id.to_bytes().to_vec(),
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| proto::InnerInstrAcct {
+                    address: meta.pubkey.to_bytes().to_vec(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: instruction.data.clone(),
+            stack_height: *stack_height,
+        }
+    }
+}
+
+impl TryFrom<proto::InnerInstrs> for ExpectedInnerInstructions {
+    type Error = FixtureError;
+
+    fn try_from(input: proto::InnerInstrs) -> Result<Self, Self::Error> {
+        let proto::InnerInstrs { instrs, prefix } = input;
+
+        let instrs = instrs
+            .into_iter()
+            .map(InnerInstruction::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(if prefix {
+            Self::Prefix(instrs)
+        } else {
+            Self::Exact(instrs)
+        })
+    }
+}
+
+impl From<&ExpectedInnerInstructions> for proto::InnerInstrs {
+    fn from(input: &ExpectedInnerInstructions) -> Self {
+        let (instrs, prefix) = match input {
+            ExpectedInnerInstructions::Exact(instrs) => (instrs, false),
+            ExpectedInnerInstructions::Prefix(instrs) => (instrs, true),
+        };
+
+        Self {
+            instrs: instrs.iter().map(Into::into).collect(),
+            prefix,
+        }
+    }
+}
+
+impl TryFrom<proto::ReturnData> for TransactionReturnData {
+    type Error = FixtureError;
+
+    fn try_from(input: proto::ReturnData) -> Result<Self, Self::Error> {
+        let proto::ReturnData { program_id, data } = input;
+
+        Ok(Self {
+            program_id: to_pubkey(program_id)?,
+            data,
+        })
+    }
+}
+
+impl From<&TransactionReturnData> for proto::ReturnData {
+    fn from(input: &TransactionReturnData) -> Self {
+        Self {
+            program_id: input.program_id.to_bytes().to_vec(),
+            data: input.data.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::ModifiedProgram> for ModifiedProgram {
+    type Error = FixtureError;
+
+    fn try_from(input: proto::ModifiedProgram) -> Result<Self, Self::Error> {
+        let proto::ModifiedProgram {
+            program_id,
+            deployment_slot,
+            status,
+        } = input;
+
+        let status = match proto::ModifiedProgramStatus::from_i32(status) {
+            Some(proto::ModifiedProgramStatus::Deployed) => ModifiedProgramStatus::Deployed,
+            Some(proto::ModifiedProgramStatus::Closed) => ModifiedProgramStatus::Closed,
+            Some(proto::ModifiedProgramStatus::FailedVerification) => {
+                ModifiedProgramStatus::FailedVerification
+            }
+            None => return Err(FixtureError::InvalidModifiedProgramStatus),
+        };
+
+        Ok(Self {
+            program_id: to_pubkey(program_id)?,
+            deployment_slot,
+            status,
+        })
+    }
+}
+
+impl From<&ModifiedProgram> for proto::ModifiedProgram {
+    fn from(input: &ModifiedProgram) -> Self {
+        let status = match input.status {
+            ModifiedProgramStatus::Deployed => proto::ModifiedProgramStatus::Deployed,
+            ModifiedProgramStatus::Closed => proto::ModifiedProgramStatus::Closed,
+            ModifiedProgramStatus::FailedVerification => {
+                proto::ModifiedProgramStatus::FailedVerification
+            }
+        };
+
+        Self {
+            program_id: input.program_id.to_bytes().to_vec(),
+            deployment_slot: input.deployment_slot,
+            status: status as i32,
+        }
+    }
 }
 
 impl TryFrom<proto::InstrEffects> for FixtureEffects {
@@ -23,25 +327,172 @@ impl TryFrom<proto::InstrEffects> for FixtureEffects {
             result,
             custom_err: custom_error,
             modified_accounts,
+            return_data,
+            inner_instrs,
+            modified_programs,
         } = input;
 
+        let result = instr_error::decode_result(result, custom_error)?;
+
         let modified_accounts = modified_accounts
             .into_iter()
             .map(|acct_state| acct_state.try_into())
             .collect::<Result<Vec<_>, _>>()?;
 
+        let return_data = return_data.map(|data| data.try_into()).transpose()?;
+
+        let inner_instructions = inner_instrs
+            .map(|inner_instrs| inner_instrs.try_into())
+            .transpose()?;
+
+        let modified_programs = modified_programs
+            .map(|modified_programs| {
+                modified_programs
+                    .programs
+                    .into_iter()
+                    .map(ModifiedProgram::try_from)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
         Ok(Self {
             result,
-            custom_error,
             modified_accounts,
+            return_data,
+            inner_instructions,
+            modified_programs,
         })
     }
 }
 
+impl From<&FixtureEffects> for proto::InstrEffects {
+    fn from(input: &FixtureEffects) -> Self {
+        let (result, custom_err) = instr_error::encode_result(&input.result);
+
+        Self {
+            result,
+            custom_err,
+            modified_accounts: input.modified_accounts.iter().map(Into::into).collect(),
+            return_data: input.return_data.as_ref().map(Into::into),
+            inner_instrs: input.inner_instructions.as_ref().map(Into::into),
+            modified_programs: input.modified_programs.as_ref().map(|modified_programs| {
+                proto::ModifiedPrograms {
+                    programs: modified_programs.iter().map(Into::into).collect(),
+                }
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {super::*, solana_sdk::account::Account};
 
+    #[test]
+    fn test_expected_inner_instructions_matches() {
+        let inner_instruction = |data: u8| InnerInstruction {
+            instruction: Instruction::new_with_bytes(Pubkey::new_unique(), &[data], vec![]),
+            stack_height: 2,
+        };
+        let first = inner_instruction(1);
+        let second = inner_instruction(2);
+        let actual = [first.clone(), second.clone()];
+
+        assert!(ExpectedInnerInstructions::Exact(actual.to_vec()).matches(&actual));
+        assert!(!ExpectedInnerInstructions::Exact(vec![first.clone()]).matches(&actual));
+        assert!(ExpectedInnerInstructions::Prefix(vec![first.clone()]).matches(&actual));
+        assert!(ExpectedInnerInstructions::Prefix(vec![]).matches(&actual));
+        assert!(!ExpectedInnerInstructions::Prefix(vec![second]).matches(&actual));
+        assert!(!ExpectedInnerInstructions::Prefix(actual.to_vec()).matches(&[first]));
+    }
+
+    #[test]
+    fn test_mismatch_borsh_io_error() {
+        let effects = FixtureEffects {
+            result: instr_error::decode_result(45, 0).unwrap(),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        };
+        let mut result = ExecutionResult {
+            result: Err(InstructionError::BorshIoError(String::from(
+                "Unexpected length of input",
+            ))),
+            compute_units_consumed: 0,
+            resulting_accounts: vec![],
+            program_load_error: None,
+            modified_programs: vec![],
+            return_data: None,
+            inner_instructions: vec![],
+            backtrace: None,
+        };
+        assert!(effects.mismatch(&result).is_none());
+
+        result.result = Err(InstructionError::GenericError);
+        assert!(matches!(
+            effects.mismatch(&result),
+            Some(EffectsMismatch::Result { .. })
+        ));
+    }
+
+    #[test]
+    fn test_instr_effects_round_trip() {
+        let program_id = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let effects = FixtureEffects {
+            result: Err(InstructionError::Custom(6003)),
+            modified_accounts: vec![(
+                account,
+                AccountSharedData::from(Account {
+                    lamports: 42,
+                    data: vec![1, 2, 3],
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 7,
+                }),
+            )],
+            return_data: Some(TransactionReturnData {
+                program_id,
+                data: vec![4, 5],
+            }),
+            inner_instructions: Some(ExpectedInnerInstructions::Prefix(vec![InnerInstruction {
+                instruction: Instruction::new_with_bytes(
+                    program_id,
+                    &[6],
+                    vec![AccountMeta::new(account, true)],
+                ),
+                stack_height: 2,
+            }])),
+            modified_programs: Some(vec![ModifiedProgram {
+                program_id,
+                deployment_slot: 42,
+                status: ModifiedProgramStatus::FailedVerification,
+            }]),
+        };
+
+        let round_trip = FixtureEffects::try_from(proto::InstrEffects::from(&effects)).unwrap();
+        assert_eq!(round_trip.result, effects.result);
+        assert_eq!(round_trip.modified_accounts, effects.modified_accounts);
+        assert_eq!(round_trip.return_data, effects.return_data);
+        assert_eq!(round_trip.inner_instructions, effects.inner_instructions);
+        assert_eq!(round_trip.modified_programs, effects.modified_programs);
+
+        // Unchecked effects stay unchecked.
+        let effects = FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        };
+        let round_trip = FixtureEffects::try_from(proto::InstrEffects::from(&effects)).unwrap();
+        assert_eq!(round_trip.result, Ok(()));
+        assert_eq!(round_trip.return_data, None);
+        assert_eq!(round_trip.inner_instructions, None);
+        assert_eq!(round_trip.modified_programs, None);
+    }
+
     #[test]
     fn test_try_from_proto_instr_effects() {
         let address1 = Pubkey::new_unique();
@@ -49,8 +500,6 @@ mod tests {
         let address2 = Pubkey::new_unique();
         let owner2 = Pubkey::new_unique();
 
-        let result = 0;
-        let custom_error = 0;
         let modified_accounts = vec![
             proto::AcctState {
                 address: address1.to_bytes().to_vec(),
@@ -71,15 +520,21 @@ mod tests {
         ];
 
         let input = proto::InstrEffects {
-            result,
-            custom_err: custom_error,
+            result: 0,
+            custom_err: 0,
             modified_accounts,
+            return_data: None,
+            inner_instrs: None,
+            modified_programs: None,
         };
 
-        let effects = FixtureEffects::try_from(input).unwrap();
-        assert_eq!(effects.result, result);
-        assert_eq!(effects.custom_error, custom_error);
+        // Success
+        let effects = FixtureEffects::try_from(input.clone()).unwrap();
+        assert_eq!(effects.result, Ok(()));
         assert_eq!(effects.modified_accounts.len(), 2);
+        assert_eq!(effects.return_data, None);
+        assert_eq!(effects.inner_instructions, None);
+        assert_eq!(effects.modified_programs, None);
 
         let (pubkey, account) = &effects.modified_accounts[0];
         assert_eq!(*pubkey, address1);
@@ -106,5 +561,141 @@ mod tests {
                 rent_epoch: 0,
             })
         );
+
+        // Failure result
+        let effects = FixtureEffects::try_from(proto::InstrEffects {
+            result: 26,
+            custom_err: 6003,
+            ..input.clone()
+        })
+        .unwrap();
+        assert_eq!(effects.result, Err(InstructionError::Custom(6003)));
+
+        // Return data
+        let program_id = Pubkey::new_unique();
+        let effects = FixtureEffects::try_from(proto::InstrEffects {
+            return_data: Some(proto::ReturnData {
+                program_id: program_id.to_bytes().to_vec(),
+                data: vec![1, 2, 3],
+            }),
+            ..input.clone()
+        })
+        .unwrap();
+        assert_eq!(
+            effects.return_data,
+            Some(TransactionReturnData {
+                program_id,
+                data: vec![1, 2, 3],
+            })
+        );
+
+        // Invalid return data program ID
+        assert_eq!(
+            FixtureEffects::try_from(proto::InstrEffects {
+                return_data: Some(proto::ReturnData {
+                    program_id: vec![1, 2, 3],
+                    data: vec![],
+                }),
+                ..input.clone()
+            })
+            .unwrap_err(),
+            FixtureError::InvalidPubkeyBytes
+        );
+
+        // Inner instructions
+        let program_id = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let inner_instrs = proto::InnerInstrs {
+            instrs: vec![proto::InnerInstr {
+                program_id: program_id.to_bytes().to_vec(),
+                accounts: vec![proto::InnerInstrAcct {
+                    address: account.to_bytes().to_vec(),
+                    is_signer: true,
+                    is_writable: false,
+                }],
+                data: vec![4, 5],
+                stack_height: 2,
+            }],
+            prefix: true,
+        };
+        let effects = FixtureEffects::try_from(proto::InstrEffects {
+            inner_instrs: Some(inner_instrs.clone()),
+            ..input.clone()
+        })
+        .unwrap();
+        let inner_instruction = InnerInstruction {
+            instruction: Instruction::new_with_bytes(
+                program_id,
+                &[4, 5],
+                vec![AccountMeta::new_readonly(account, true)],
+            ),
+            stack_height: 2,
+        };
+        assert_eq!(
+            effects.inner_instructions,
+            Some(ExpectedInnerInstructions::Prefix(vec![
+                inner_instruction.clone()
+            ]))
+        );
+        let effects = FixtureEffects::try_from(proto::InstrEffects {
+            inner_instrs: Some(proto::InnerInstrs {
+                prefix: false,
+                ..inner_instrs
+            }),
+            ..input.clone()
+        })
+        .unwrap();
+        assert_eq!(
+            effects.inner_instructions,
+            Some(ExpectedInnerInstructions::Exact(vec![inner_instruction]))
+        );
+
+        // Modified programs
+        let program_id = Pubkey::new_unique();
+        let modified_program = proto::ModifiedProgram {
+            program_id: program_id.to_bytes().to_vec(),
+            deployment_slot: 42,
+            status: proto::ModifiedProgramStatus::Closed as i32,
+        };
+        let effects = FixtureEffects::try_from(proto::InstrEffects {
+            modified_programs: Some(proto::ModifiedPrograms {
+                programs: vec![modified_program.clone()],
+            }),
+            ..input.clone()
+        })
+        .unwrap();
+        assert_eq!(
+            effects.modified_programs,
+            Some(vec![ModifiedProgram {
+                program_id,
+                deployment_slot: 42,
+                status: ModifiedProgramStatus::Closed,
+            }])
+        );
+
+        // Invalid modified program status
+        assert_eq!(
+            FixtureEffects::try_from(proto::InstrEffects {
+                modified_programs: Some(proto::ModifiedPrograms {
+                    programs: vec![proto::ModifiedProgram {
+                        status: -1,
+                        ..modified_program
+                    }],
+                }),
+                ..input.clone()
+            })
+            .unwrap_err(),
+            FixtureError::InvalidModifiedProgramStatus
+        );
+
+        // Invalid result code
+        assert_eq!(
+            FixtureEffects::try_from(proto::InstrEffects {
+                result: -1,
+                ..input
+            })
+            .unwrap_err(),
+            FixtureError::InvalidResultCode
+        );
     }
 }
@@ -28,4 +28,31 @@ pub enum FixtureError {
     /// The output fixture is invalid.
     #[error("Invalid fixture output")]
     InvalidFixtureOutput,
+    /// A provided program status is invalid.
+    #[error("Invalid program status")]
+    InvalidProgramStatus,
+    /// A provided modified program status is invalid.
+    #[error("Invalid modified program status")]
+    InvalidModifiedProgramStatus,
+    /// The program account, or its program data account, is missing from the
+    /// input accounts or invalid.
+    #[error("Invalid program account")]
+    InvalidProgramAccount,
+    /// A provided result code does not correspond to an `InstructionError`.
+    #[error("Invalid result code")]
+    InvalidResultCode,
+    /// A custom error code is provided with a result code other than that
+    /// of `InstructionError::Custom`.
+    #[error("Unexpected custom error code")]
+    UnexpectedCustomError,
+    /// A provided feature is not supported by the harness.
+    #[error("Unsupported feature")]
+    UnsupportedFeature,
+    /// Too many features were provided to execute every combination.
+    #[error("Too many features")]
+    TooManyFeatures,
+    /// The fixture's own instruction fails, where a passing fixture is
+    /// required.
+    #[error("Baseline execution failed")]
+    BaselineFailed,
 }
@@ -78,6 +78,12 @@ static AGAVE_FEATURES: &[Pubkey] = &[
     deprecate_executable_meta_update_in_bpf_loader::id(),
 ];
 
+/// The feature IDs supported by the harness. Fixtures can only activate
+/// features from this list.
+pub fn supported_features() -> &'static [Pubkey] {
+    AGAVE_FEATURES
+}
+
 impl From<proto::FeatureSet> for FeatureSet {
     fn from(input: proto::FeatureSet) -> Self {
         let mut feature_set = FeatureSet::default();
@@ -0,0 +1,201 @@
+//! Encoding of instruction results as fixture result codes, following the
+//! convention shared by solfuzz and Firedancer.
+//!
+//! A successful instruction has a result code of zero. A failed instruction
+//! has a result code of its `InstructionError` variant index plus one, and
+//! `InstructionError::Custom` additionally carries its payload as the custom
+//! error code. The message of `InstructionError::BorshIoError` isn't encoded,
+//! so results are compared by their codes.
+
+use {
+    super::error::FixtureError,
+    solana_sdk::{instruction::InstructionError, program_error::ProgramError},
+};
+
+/// Encode an `InstructionError` as a result code.
+pub fn error_to_result_code(error: &InstructionError) -> i32 {
+    // Bincode serializes the variant index as a leading little-endian `u32`.
+    let serialized_error = bincode::serialize(error).unwrap();
+    i32::from_le_bytes(serialized_error[..4].try_into().unwrap()) + 1
+}
+
+/// Encode an instruction result as a `(result, custom_error)` pair.
+pub fn encode_result(result: &Result<(), InstructionError>) -> (i32, u64) {
+    match result {
+        Ok(()) => (0, 0),
+        Err(error @ InstructionError::Custom(code)) => {
+            (error_to_result_code(error), u64::from(*code))
+        }
+        Err(error) => (error_to_result_code(error), 0),
+    }
+}
+
+/// Decode a result code and custom error code into an `InstructionError`.
+pub fn result_code_to_error(
+    result_code: i32,
+    custom_error: u64,
+) -> Result<InstructionError, FixtureError> {
+    let variant_index = result_code
+        .checked_sub(1)
+        .and_then(|index| u32::try_from(index).ok())
+        .ok_or(FixtureError::InvalidResultCode)?;
+    let mut serialized_error = variant_index.to_le_bytes().to_vec();
+    if result_code == error_to_result_code(&InstructionError::Custom(0)) {
+        let custom_error =
+            u32::try_from(custom_error).map_err(|_| FixtureError::IntegerOutOfRange)?;
+        serialized_error.extend_from_slice(&custom_error.to_le_bytes());
+    } else if custom_error != 0 {
+        return Err(FixtureError::UnexpectedCustomError);
+    } else {
+        // An empty string, for `InstructionError::BorshIoError`.
+        serialized_error.extend_from_slice(&0u64.to_le_bytes());
+    }
+    bincode::deserialize(&serialized_error).map_err(|_| FixtureError::InvalidResultCode)
+}
+
+/// Decode a `(result, custom_error)` pair into an instruction result.
+pub fn decode_result(
+    result_code: i32,
+    custom_error: u64,
+) -> Result<Result<(), InstructionError>, FixtureError> {
+    if result_code == 0 {
+        if custom_error != 0 {
+            return Err(FixtureError::UnexpectedCustomError);
+        }
+        Ok(Ok(()))
+    } else {
+        result_code_to_error(result_code, custom_error).map(Err)
+    }
+}
+
+/// Whether two instruction results encode to the same result code and
+/// custom error code, ignoring the message of `InstructionError::BorshIoError`.
+pub fn results_match(
+    expected: &Result<(), InstructionError>,
+    actual: &Result<(), InstructionError>,
+) -> bool {
+    encode_result(expected) == encode_result(actual)
+}
+
+/// Convert a `ProgramError` into the `InstructionError` reported by the
+/// runtime when a program returns it.
+pub fn program_error_to_instruction_error(error: ProgramError) -> InstructionError {
+    InstructionError::from(u64::from(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_result() {
+        assert_eq!(encode_result(&Ok(())), (0, 0));
+        assert_eq!(encode_result(&Err(InstructionError::GenericError)), (1, 0));
+        assert_eq!(
+            encode_result(&Err(InstructionError::IncorrectProgramId)),
+            (7, 0)
+        );
+        assert_eq!(
+            encode_result(&Err(InstructionError::AccountBorrowFailed)),
+            (23, 0)
+        );
+        assert_eq!(encode_result(&Err(InstructionError::Custom(42))), (26, 42));
+        assert_eq!(
+            encode_result(&Err(InstructionError::ComputationalBudgetExceeded)),
+            (38, 0)
+        );
+        assert_eq!(
+            encode_result(&Err(InstructionError::BorshIoError(String::from("error")))),
+            (45, 0)
+        );
+        assert_eq!(
+            encode_result(&Err(
+                InstructionError::BuiltinProgramsMustConsumeComputeUnits
+            )),
+            (54, 0)
+        );
+    }
+
+    #[test]
+    fn test_decode_result() {
+        assert_eq!(decode_result(0, 0), Ok(Ok(())));
+        assert_eq!(decode_result(1, 0), Ok(Err(InstructionError::GenericError)));
+        assert_eq!(
+            decode_result(7, 0),
+            Ok(Err(InstructionError::IncorrectProgramId))
+        );
+        assert_eq!(decode_result(26, 42), Ok(Err(InstructionError::Custom(42))));
+        assert_eq!(
+            decode_result(45, 0),
+            Ok(Err(InstructionError::BorshIoError(String::new())))
+        );
+        assert_eq!(
+            decode_result(54, 0),
+            Ok(Err(
+                InstructionError::BuiltinProgramsMustConsumeComputeUnits
+            ))
+        );
+
+        // Round trip every encodable error.
+        for code in 1..=54 {
+            let result = decode_result(code, 0).unwrap();
+            assert_eq!(encode_result(&result), (code, 0));
+        }
+
+        // Fail invalid result codes
+        assert_eq!(decode_result(-1, 0), Err(FixtureError::InvalidResultCode));
+        assert_eq!(decode_result(55, 0), Err(FixtureError::InvalidResultCode));
+
+        // Fail custom error code out of range
+        assert_eq!(
+            decode_result(26, u64::from(u32::MAX) + 1),
+            Err(FixtureError::IntegerOutOfRange)
+        );
+
+        // Fail custom error code without a custom error
+        assert_eq!(
+            decode_result(0, 42),
+            Err(FixtureError::UnexpectedCustomError)
+        );
+        assert_eq!(
+            decode_result(1, 42),
+            Err(FixtureError::UnexpectedCustomError)
+        );
+    }
+
+    #[test]
+    fn test_results_match() {
+        // Decoded fixtures don't carry the runtime's message.
+        assert!(results_match(
+            &decode_result(45, 0).unwrap(),
+            &Err(InstructionError::BorshIoError(String::from(
+                "Unexpected length"
+            ))),
+        ));
+        assert!(results_match(&Ok(()), &Ok(())));
+        assert!(!results_match(
+            &Err(InstructionError::Custom(1)),
+            &Err(InstructionError::Custom(2)),
+        ));
+        assert!(!results_match(
+            &Err(InstructionError::BorshIoError(String::new())),
+            &Err(InstructionError::GenericError),
+        ));
+    }
+
+    #[test]
+    fn test_program_error_to_instruction_error() {
+        assert_eq!(
+            program_error_to_instruction_error(ProgramError::IncorrectProgramId),
+            InstructionError::IncorrectProgramId
+        );
+        assert_eq!(
+            program_error_to_instruction_error(ProgramError::Custom(0)),
+            InstructionError::Custom(0)
+        );
+        assert_eq!(
+            program_error_to_instruction_error(ProgramError::Custom(6003)),
+            InstructionError::Custom(6003)
+        );
+    }
+}
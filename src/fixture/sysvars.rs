@@ -31,6 +31,20 @@ pub struct FixtureSysvarContext {
     pub stake_history: StakeHistory,
 }
 
+// `SlotHashes` does not implement `Clone`.
+impl Clone for FixtureSysvarContext {
+    fn clone(&self) -> Self {
+        Self {
+            clock: self.clock.clone(),
+            epoch_rewards: self.epoch_rewards,
+            epoch_schedule: self.epoch_schedule.clone(),
+            rent: self.rent.clone(),
+            slot_hashes: SlotHashes::new(self.slot_hashes.slot_hashes()),
+            stake_history: self.stake_history.clone(),
+        }
+    }
+}
+
 impl From<proto::Clock> for Clock {
     fn from(input: proto::Clock) -> Self {
         Self {
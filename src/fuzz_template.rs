@@ -0,0 +1,257 @@
+//! Instruction-data fuzzing from a template fixture.
+//!
+//! A known-valid fixture serves as a template: its accounts, sysvars and
+//! feature set are held fixed, while fuzz inputs drive the instruction data,
+//! and optionally the data of selected accounts. Fuzz campaigns can then
+//! focus on a single instruction's parsing under realistic state.
+//!
+//! A fuzz input is laid out as:
+//!
+//! * The instruction data length, as a little-endian `u16`, capped to the
+//!   remaining input.
+//! * The instruction data.
+//! * Data overwriting the selected accounts' data in order, from the start
+//!   of each account's data. Accounts keep their template data length, and
+//!   any bytes not covered by the input keep their template values.
+//!
+//! With a schema mutator, the selected accounts' data is instead mutated
+//! field by field, driven by the rest of the input in triples of bytes,
+//! choosing an account, a field and a mutation.
+
+use {
+    crate::{
+        account_schema::SchemaMutator,
+        fixture::{context::FixtureContext, error::FixtureError, Fixture},
+    },
+    solana_sdk::{
+        account::{ReadableAccount, WritableAccount},
+        pubkey::Pubkey,
+    },
+};
+
+// The size of the instruction data length prefix.
+const LENGTH_PREFIX_SIZE: usize = 2;
+
+/// A template fixture for fuzzing instruction data.
+#[derive(Clone, Debug)]
+pub struct FuzzTemplate {
+    context: FixtureContext,
+    // Indices into the context's accounts of the accounts whose data is
+    // mutated.
+    mutable_accounts: Vec<usize>,
+    schema_mutator: Option<SchemaMutator>,
+}
+
+impl FuzzTemplate {
+    /// Create a template from a known-valid fixture, optionally with
+    /// accounts whose data is mutated by fuzz inputs too.
+    ///
+    /// Fails with `FixtureError::AccountMissing` if a mutable account is not
+    /// one of the fixture's input accounts.
+    pub fn new(fixture: Fixture, mutable_accounts: &[Pubkey]) -> Result<Self, FixtureError> {
+        let context = fixture.input;
+        let mutable_accounts = mutable_accounts
+            .iter()
+            .map(|pubkey| {
+                context
+                    .accounts
+                    .iter()
+                    .position(|(key, _)| key == pubkey)
+                    .ok_or(FixtureError::AccountMissing)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            context,
+            mutable_accounts,
+            schema_mutator: None,
+        })
+    }
+
+    /// Mutate the selected accounts' data according to their schemas,
+    /// rather than overwriting it. Public key fields are swapped among the
+    /// mutator's known keys, the template's account keys and the program
+    /// ID.
+    pub fn with_schema_mutator(mut self, schema_mutator: SchemaMutator) -> Self {
+        let mut known_keys = vec![self.context.program_id];
+        known_keys.extend(self.context.accounts.iter().map(|(pubkey, _)| *pubkey));
+        self.schema_mutator = Some(schema_mutator.with_known_keys(&known_keys));
+        self
+    }
+
+    /// The template's fixture context.
+    pub fn context(&self) -> &FixtureContext {
+        &self.context
+    }
+
+    /// The fixture context for a fuzz input, with the template's accounts,
+    /// sysvars and feature set.
+    pub fn instantiate(&self, data: &[u8]) -> FixtureContext {
+        let mut context = self.context.clone();
+
+        let (length, rest) = match data.get(..LENGTH_PREFIX_SIZE) {
+            Some(length) => (
+                u16::from_le_bytes(length.try_into().unwrap()) as usize,
+                &data[LENGTH_PREFIX_SIZE..],
+            ),
+            None => (0, &[][..]),
+        };
+        let (instruction_data, mut account_data) = rest.split_at(length.min(rest.len()));
+        context.instruction_data = instruction_data.to_vec();
+
+        match &self.schema_mutator {
+            Some(schema_mutator) if !self.mutable_accounts.is_empty() => {
+                for triple in account_data.chunks_exact(3) {
+                    let index =
+                        self.mutable_accounts[triple[0] as usize % self.mutable_accounts.len()];
+                    let data = context.accounts[index].1.data_as_mut_slice();
+                    schema_mutator.mutate_field(data, triple[1], triple[2]);
+                }
+            }
+            Some(_) => {}
+            None => {
+                for index in &self.mutable_accounts {
+                    let data = context.accounts[*index].1.data_as_mut_slice();
+                    let len = data.len().min(account_data.len());
+                    data[..len].copy_from_slice(&account_data[..len]);
+                    account_data = &account_data[len..];
+                }
+            }
+        }
+        context
+    }
+
+    /// The fuzz input reproducing the template's own instruction data and
+    /// account data, to seed a fuzz corpus. With a schema mutator, the
+    /// account data is reproduced by applying no mutations.
+    pub fn seed(&self) -> Vec<u8> {
+        let instruction_data = &self.context.instruction_data;
+        let length = instruction_data.len().min(u16::MAX as usize);
+        let mut seed = (length as u16).to_le_bytes().to_vec();
+        seed.extend_from_slice(&instruction_data[..length]);
+        if self.schema_mutator.is_some() {
+            return seed;
+        }
+        for index in &self.mutable_accounts {
+            seed.extend_from_slice(self.context.accounts[*index].1.data());
+        }
+        seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            account_schema::{AccountSchema, FieldType},
+            fixture::{
+                context::ProgramStatus, effects::FixtureEffects, sysvars::FixtureSysvarContext,
+            },
+        },
+        solana_sdk::{
+            account::{Account, AccountSharedData},
+            bpf_loader_upgradeable,
+            feature_set::FeatureSet,
+            instruction::AccountMeta,
+        },
+    };
+
+    fn account(data: Vec<u8>) -> AccountSharedData {
+        AccountSharedData::from(Account {
+            lamports: 1_000_000,
+            data,
+            ..Account::default()
+        })
+    }
+
+    fn template_fixture(first: Pubkey, second: Pubkey) -> Fixture {
+        Fixture {
+            input: FixtureContext {
+                program_id: Pubkey::new_unique(),
+                loader_id: bpf_loader_upgradeable::id(),
+                feature_set: FeatureSet::all_enabled(),
+                sysvar_context: FixtureSysvarContext::default(),
+                accounts: vec![(first, account(vec![1; 4])), (second, account(vec![2; 3]))],
+                instruction_accounts: vec![
+                    AccountMeta::new(first, false),
+                    AccountMeta::new(second, false),
+                ],
+                instruction_data: vec![9, 9],
+                program_status: ProgramStatus::Deployed,
+                upgrade_authority: None,
+                deployment_slot: None,
+            },
+            output: FixtureEffects {
+                result: Ok(()),
+                modified_accounts: vec![],
+                return_data: None,
+                inner_instructions: None,
+                modified_programs: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let fixture = || template_fixture(first, second);
+
+        // Only the instruction data is mutated.
+        let template = FuzzTemplate::new(fixture(), &[]).unwrap();
+        let context = template.instantiate(&[3, 0, 7, 7, 7, 8]);
+        assert_eq!(context.instruction_data, vec![7, 7, 7]);
+        assert_eq!(context.accounts, fixture().input.accounts);
+        assert!(template.instantiate(&[]).instruction_data.is_empty());
+        assert_eq!(template.instantiate(&[9, 0, 5]).instruction_data, vec![5]);
+
+        // Accounts keep their data length, and template bytes beyond the
+        // input.
+        let template = FuzzTemplate::new(fixture(), &[second, first]).unwrap();
+        let context = template.instantiate(&[1, 0, 5, 6, 6, 6, 4, 4]);
+        assert_eq!(context.instruction_data, vec![5]);
+        assert_eq!(context.accounts[1].1.data(), &[6, 6, 6]);
+        assert_eq!(context.accounts[0].1.data(), &[4, 4, 1, 1]);
+
+        assert_eq!(
+            FuzzTemplate::new(fixture(), &[Pubkey::new_unique()]).unwrap_err(),
+            FixtureError::AccountMissing
+        );
+    }
+
+    #[test]
+    fn test_instantiate_with_schema_mutator() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+
+        // The second account's data begins with a discriminator of 2, then
+        // a `u16` field.
+        let schema = AccountSchema::new("Second", &[2]).with_field("value", FieldType::U16);
+        let template = FuzzTemplate::new(template_fixture(first, second), &[first, second])
+            .unwrap()
+            .with_schema_mutator(SchemaMutator::new(vec![schema]));
+
+        let context = template.instantiate(&[0, 0, 1, 0, 2, 0, 0, 0]);
+        assert!(context.instruction_data.is_empty());
+        assert_eq!(context.accounts[0].1.data(), &[1, 1, 1, 1]);
+        assert_eq!(context.accounts[1].1.data(), &[2, 0xff, 0xff]);
+
+        assert_eq!(template.seed(), vec![2, 0, 9, 9]);
+    }
+
+    #[test]
+    fn test_seed() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let template = FuzzTemplate::new(template_fixture(first, second), &[first]).unwrap();
+        let seed = template.seed();
+        assert_eq!(seed, vec![2, 0, 9, 9, 1, 1, 1, 1]);
+
+        let context = template.instantiate(&seed);
+        assert_eq!(
+            context.instruction_data,
+            template.context().instruction_data
+        );
+        assert_eq!(context.accounts, template.context().accounts);
+    }
+}
@@ -0,0 +1,465 @@
+//! A configurable harness for processing fixtures.
+
+#[cfg(feature = "native")]
+use {crate::execute_instruction_native, solana_sdk::entrypoint::ProcessInstruction};
+use {
+    crate::{
+        backtrace, context_from_accounts,
+        coverage::Coverage,
+        custom_error::CustomErrorDecoder,
+        execute,
+        fixture::{
+            context::FixtureContext,
+            effects::{EffectsMismatch, FixtureEffects},
+            error::FixtureError,
+            Fixture,
+        },
+        mock_syscalls::MockSyscall,
+        profiler::{profile_execution, ElfSymbols, Profile},
+        result::ExecutionResult,
+        syscall_faults::SyscallFault,
+        ExecuteOptions, ProgramUnderTest,
+    },
+    solana_sdk::instruction::InstructionError,
+    std::sync::Mutex,
+};
+
+/// A harness for processing fixtures, with optional program-specific
+/// configuration.
+#[derive(Default)]
+pub struct Harness {
+    /// Decoder for the program's custom error codes, used in mismatch
+    /// reports.
+    pub custom_error_decoder: Option<Box<dyn CustomErrorDecoder>>,
+    // Coverage of the SBF program, collected across executions if enabled.
+    coverage: Option<Mutex<Coverage>>,
+    // Compute unit profile of the SBF program, collected across executions
+    // if enabled.
+    profile: Option<Mutex<Profile>>,
+    // Port on which the SBF program under test waits for a debugger, if
+    // enabled.
+    #[cfg(feature = "debugger")]
+    debug_port: Option<u16>,
+    // Whether to capture backtraces of SBF program failures.
+    backtraces: bool,
+    // Faults injected into the SBF program's syscalls.
+    syscall_faults: Vec<SyscallFault>,
+    // Syscalls added to, or replaced in, the program runtime environments.
+    mock_syscalls: Vec<MockSyscall>,
+}
+
+impl Harness {
+    /// Set the decoder for the program's custom error codes.
+    pub fn with_custom_error_decoder(
+        mut self,
+        custom_error_decoder: impl CustomErrorDecoder + 'static,
+    ) -> Self {
+        self.custom_error_decoder = Some(Box::new(custom_error_decoder));
+        self
+    }
+
+    /// Collect code coverage of the SBF program under test across every
+    /// execution. Instruction tracing slows execution down.
+    pub fn with_coverage(mut self) -> Self {
+        self.coverage = Some(Mutex::new(Coverage::default()));
+        self
+    }
+
+    /// The coverage collected so far, if enabled.
+    pub fn coverage(&self) -> Option<Coverage> {
+        self.coverage
+            .as_ref()
+            .map(|coverage| coverage.lock().unwrap().clone())
+    }
+
+    /// Profile the compute units consumed by the SBF program under test
+    /// across every execution. Instruction tracing slows execution down.
+    pub fn with_profiling(mut self) -> Self {
+        self.profile = Some(Mutex::new(Profile::default()));
+        self
+    }
+
+    /// The compute unit profile collected so far, if enabled.
+    pub fn profile(&self) -> Option<Profile> {
+        self.profile
+            .as_ref()
+            .map(|profile| profile.lock().unwrap().clone())
+    }
+
+    /// Capture a source-level backtrace when the SBF program under test
+    /// fails, reported in the execution result and in mismatch reports. The
+    /// program must be built with debug info for source locations.
+    /// Instruction tracing slows execution down.
+    pub fn with_backtraces(mut self) -> Self {
+        self.backtraces = true;
+        self
+    }
+
+    /// Inject a fault into invocations of the SBF program's syscalls, to test
+    /// error paths such as a failed cross-program invocation. Faults are
+    /// matched in the order they were added, and injected into programs of
+    /// every loader, but not into native programs. Environments with more
+    /// syscalls than the harness can intercept fail to load the program.
+    pub fn with_syscall_fault(mut self, fault: SyscallFault) -> Self {
+        self.syscall_faults.push(fault);
+        self
+    }
+
+    /// Register a mock syscall in the program runtime environments, adding
+    /// a syscall the runtime lacks or replacing one of the same name, such
+    /// as a deterministic `sol_remaining_compute_units`. Mock syscalls are
+    /// not available to native programs.
+    pub fn with_mock_syscall(mut self, mock_syscall: MockSyscall) -> Self {
+        self.mock_syscalls.push(mock_syscall);
+        self
+    }
+
+    /// Execute the SBF program under test under a GDB remote stub listening
+    /// on `127.0.0.1:<debug_port>`, for stepping through instructions,
+    /// setting breakpoints and inspecting registers and memory under the
+    /// fixture's inputs.
+    ///
+    /// Each execution of the program, including through cross-program
+    /// invocations, blocks until a debugger connects, and runs in the
+    /// interpreter. Programs owned by loader v4 are not debugged.
+    #[cfg(feature = "debugger")]
+    pub fn with_debug_port(mut self, debug_port: u16) -> Self {
+        self.debug_port = Some(debug_port);
+        self
+    }
+
+    /// Describe an instruction error, decoding custom error codes when
+    /// possible, such as `MyProgramError::InsufficientCollateral (6003)`.
+    pub fn describe_error(&self, error: &InstructionError) -> String {
+        match error {
+            InstructionError::Custom(code) => {
+                match self
+                    .custom_error_decoder
+                    .as_ref()
+                    .and_then(|decoder| decoder.decode(*code))
+                {
+                    Some(decoded) => format!("{} ({}): {}", decoded.name, code, decoded.message),
+                    None => format!("Custom({})", code),
+                }
+            }
+            error => format!("{:?}", error),
+        }
+    }
+
+    /// Describe an instruction result, decoding custom error codes when
+    /// possible.
+    pub fn describe_result(&self, result: &Result<(), InstructionError>) -> String {
+        match result {
+            Ok(()) => String::from("Ok(())"),
+            Err(error) => format!("Err({})", self.describe_error(error)),
+        }
+    }
+
+    /// Process a fixture using the simulated Solana program runtime,
+    /// panicking with a report if the effects do not match.
+    pub fn process_fixture(&self, fixture: Fixture, elf: &[u8]) {
+        let Fixture { input, output } = fixture;
+        let result = self.execute_instruction(input, elf);
+        self.check_effects(output, result);
+    }
+
+    /// Process a fixture using the simulated Solana program runtime, loading
+    /// the program from the fixture's own input accounts, and panicking with
+    /// a report if the effects do not match.
+    pub fn process_fixture_from_accounts(&self, fixture: Fixture) -> Result<(), FixtureError> {
+        let Fixture { input, output } = fixture;
+        let result = self.execute_instruction_from_accounts(input)?;
+        self.check_effects(output, result);
+        Ok(())
+    }
+
+    /// Process a fixture using the simulated Solana program runtime, with the
+    /// program under test implemented by a native Rust `process_instruction`
+    /// function, panicking with a report if the effects do not match.
+    #[cfg(feature = "native")]
+    pub fn process_fixture_native(
+        &self,
+        fixture: Fixture,
+        process_instruction: ProcessInstruction,
+    ) {
+        let Fixture { input, output } = fixture;
+        let result = self.execute_instruction_native(input, process_instruction);
+        self.check_effects(output, result);
+    }
+
+    /// Process a fixture with both the SBF build and the native build of the
+    /// program under test, panicking with a report if the builds disagree or
+    /// if the effects do not match.
+    #[cfg(feature = "native")]
+    pub fn process_fixture_differential(
+        &self,
+        fixture: Fixture,
+        elf: &[u8],
+        process_instruction: ProcessInstruction,
+    ) {
+        let Fixture { input, output } = fixture;
+        let (result, native_result) =
+            self.execute_instruction_differential(input, elf, process_instruction);
+        assert!(
+            result.same_outcome(&native_result),
+            "Native build mismatch: SBF got {} with accounts {:?}, native got {} with accounts {:?}",
+            self.describe_result(&result.result),
+            result.resulting_accounts,
+            self.describe_result(&native_result.result),
+            native_result.resulting_accounts,
+        );
+        self.check_effects(output, result);
+    }
+
+    fn check_effects(&self, effects: FixtureEffects, result: ExecutionResult) {
+        let mismatch = match effects.mismatch(&result) {
+            None => return,
+            Some(EffectsMismatch::Result { expected, actual }) => format!(
+                "Instruction result mismatch: expected {}, got {}",
+                self.describe_result(&expected),
+                self.describe_result(&actual),
+            ),
+            Some(EffectsMismatch::Account {
+                pubkey,
+                expected,
+                actual,
+            }) => format!(
+                "Account {} mismatch: expected {:?}, got {:?}",
+                pubkey, expected, actual,
+            ),
+            Some(EffectsMismatch::ReturnData { expected, actual }) => format!(
+                "Return data mismatch: expected {:?}, got {:?}",
+                expected, actual,
+            ),
+            Some(EffectsMismatch::InnerInstructions { expected, actual }) => format!(
+                "Inner instructions mismatch: expected {:?}, got {:?}",
+                expected, actual,
+            ),
+            Some(EffectsMismatch::ModifiedPrograms { expected, actual }) => format!(
+                "Modified programs mismatch: expected {:?}, got {:?}",
+                expected, actual,
+            ),
+        };
+        // Any mismatch of a failed execution is reported with where it failed.
+        let backtrace = result
+            .backtrace
+            .filter(|_| result.result.is_err())
+            .map(|backtrace| format!("\n{}", backtrace))
+            .unwrap_or_default();
+        panic!("{}{}", mismatch, backtrace);
+    }
+
+    /// Execute a fixture's instruction using the simulated Solana program
+    /// runtime, returning the result rather than checking it against
+    /// expected effects.
+    pub fn execute_instruction(&self, context: FixtureContext, elf: &[u8]) -> ExecutionResult {
+        let program_id = context.program_id;
+        let (mut result, debug_output) = execute(
+            context,
+            ProgramUnderTest::Elf(elf),
+            ExecuteOptions {
+                debugging_features: self.coverage.is_some()
+                    || self.profile.is_some()
+                    || self.backtraces,
+                #[cfg(feature = "debugger")]
+                debug_port: self.debug_port,
+                syscall_faults: &self.syscall_faults,
+                mock_syscalls: &self.mock_syscalls,
+            },
+        );
+        let traces = &debug_output.traces;
+        if let Some(coverage) = &self.coverage {
+            let mut coverage = coverage.lock().unwrap();
+            traces.iter().for_each(|trace| coverage.record_trace(trace));
+        }
+        if let Some(profile) = &self.profile {
+            // Programs which fail to parse also fail to load, so there is
+            // nothing to profile.
+            if let Ok(symbols) = ElfSymbols::parse(elf) {
+                let invocations = std::iter::once((program_id, 1))
+                    .chain(result.inner_instructions.iter().map(|inner_instruction| {
+                        (
+                            inner_instruction.instruction.program_id,
+                            inner_instruction.stack_height,
+                        )
+                    }))
+                    .collect::<Vec<_>>();
+                profile.lock().unwrap().merge(&profile_execution(
+                    &symbols,
+                    &program_id,
+                    &invocations,
+                    traces,
+                    result.compute_units_consumed,
+                ));
+            }
+        }
+        if self.backtraces && result.result.is_err() {
+            result.backtrace = backtrace::capture(elf, traces, &debug_output.logs);
+        }
+        result
+    }
+
+    /// Execute a fixture's instruction using the simulated Solana program
+    /// runtime, loading the program from the fixture's own input accounts.
+    pub fn execute_instruction_from_accounts(
+        &self,
+        context: FixtureContext,
+    ) -> Result<ExecutionResult, FixtureError> {
+        let (context, elf) = context_from_accounts(context)?;
+        Ok(self.execute_instruction(context, &elf))
+    }
+
+    /// Execute a fixture's instruction using the simulated Solana program
+    /// runtime, with the program under test implemented by a native Rust
+    /// `process_instruction` function.
+    #[cfg(feature = "native")]
+    pub fn execute_instruction_native(
+        &self,
+        context: FixtureContext,
+        process_instruction: ProcessInstruction,
+    ) -> ExecutionResult {
+        execute_instruction_native(context, process_instruction)
+    }
+
+    // Execute a passing fixture's instruction, then once for each variant
+    // applied to its context, returning the baseline result and the variants
+    // whose result `keep` accepts when compared with the baseline.
+    pub(crate) fn scan_variants<V>(
+        &self,
+        context: &FixtureContext,
+        elf: &[u8],
+        variants: Vec<V>,
+        apply: impl Fn(&V, &mut FixtureContext),
+        keep: impl Fn(&ExecutionResult, &ExecutionResult) -> bool,
+    ) -> Result<(ExecutionResult, Vec<(V, ExecutionResult)>), FixtureError> {
+        let baseline = self.execute_instruction(context.clone(), elf);
+        if baseline.result.is_err() {
+            return Err(FixtureError::BaselineFailed);
+        }
+
+        let kept = variants
+            .into_iter()
+            .filter_map(|variant| {
+                let mut context = context.clone();
+                apply(&variant, &mut context);
+                let result = self.execute_instruction(context, elf);
+                keep(&result, &baseline).then_some((variant, result))
+            })
+            .collect();
+        Ok((baseline, kept))
+    }
+
+    /// Execute a fixture's instruction with both the SBF build and the native
+    /// build of the program under test, returning both results.
+    #[cfg(feature = "native")]
+    pub fn execute_instruction_differential(
+        &self,
+        context: FixtureContext,
+        elf: &[u8],
+        process_instruction: ProcessInstruction,
+    ) -> (ExecutionResult, ExecutionResult) {
+        (
+            self.execute_instruction(context.clone(), elf),
+            self.execute_instruction_native(context, process_instruction),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            backtrace::{Backtrace, PanicLocation},
+            custom_error::{AnchorErrorDecoder, DecodedCustomError},
+        },
+        solana_sdk::{account::AccountSharedData, pubkey::Pubkey},
+        std::panic::{catch_unwind, AssertUnwindSafe},
+    };
+
+    #[test]
+    fn test_describe_result() {
+        let harness = Harness::default();
+        assert_eq!(harness.describe_result(&Ok(())), "Ok(())");
+        assert_eq!(
+            harness.describe_result(&Err(InstructionError::InvalidAccountData)),
+            "Err(InvalidAccountData)"
+        );
+        assert_eq!(
+            harness.describe_result(&Err(InstructionError::Custom(6003))),
+            "Err(Custom(6003))"
+        );
+
+        let harness = Harness::default().with_custom_error_decoder((AnchorErrorDecoder, |code| {
+            (code == 6003).then(|| DecodedCustomError {
+                name: String::from("MyProgramError::InsufficientCollateral"),
+                message: String::from("Not enough collateral"),
+            })
+        }));
+        assert_eq!(
+            harness.describe_result(&Err(InstructionError::Custom(6003))),
+            "Err(MyProgramError::InsufficientCollateral (6003): Not enough collateral)"
+        );
+        assert_eq!(
+            harness.describe_result(&Err(InstructionError::Custom(2000))),
+            "Err(anchor_lang::ErrorCode::ConstraintMut (2000): A mut constraint was violated)"
+        );
+        assert_eq!(
+            harness.describe_result(&Err(InstructionError::Custom(6004))),
+            "Err(Custom(6004))"
+        );
+    }
+
+    #[test]
+    fn test_check_effects_backtrace() {
+        let harness = Harness::default();
+        let pubkey = Pubkey::new_unique();
+        let effects = |result| FixtureEffects {
+            result,
+            modified_accounts: vec![(pubkey, AccountSharedData::new(1, 0, &pubkey))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        };
+        let result = ExecutionResult {
+            result: Err(InstructionError::Custom(1)),
+            compute_units_consumed: 0,
+            resulting_accounts: vec![(pubkey, AccountSharedData::new(2, 0, &pubkey))],
+            program_load_error: None,
+            modified_programs: vec![],
+            return_data: None,
+            inner_instructions: vec![],
+            backtrace: Some(Backtrace {
+                panic_location: Some(PanicLocation {
+                    file: String::from("src/lib.rs"),
+                    line: 1,
+                    column: 2,
+                }),
+                frames: vec![],
+            }),
+        };
+        let message = |effects: FixtureEffects, result: ExecutionResult| {
+            let payload = catch_unwind(AssertUnwindSafe(|| harness.check_effects(effects, result)))
+                .unwrap_err();
+            payload
+                .downcast::<String>()
+                .map(|message| *message)
+                .unwrap()
+        };
+
+        // A mismatch other than the result still reports the backtrace.
+        let report = message(effects(Err(InstructionError::Custom(1))), result.clone());
+        assert!(report.starts_with(&format!("Account {} mismatch", pubkey)));
+        assert!(report.contains("panicked at src/lib.rs:1:2"));
+
+        // Successful executions have no backtrace to report.
+        let report = message(
+            effects(Ok(())),
+            ExecutionResult {
+                result: Ok(()),
+                ..result
+            },
+        );
+        assert!(!report.contains("panicked at"));
+    }
+}
@@ -3,31 +3,211 @@
 #![deny(missing_docs)]
 #![cfg_attr(not(test), forbid(unsafe_code))]
 
+pub mod account_schema;
+pub mod account_substitution;
+pub mod backtrace;
+pub mod coverage;
+pub mod custom_error;
+pub mod feature_matrix;
 pub mod fixture;
+pub mod fuzz_template;
+pub mod harness;
+pub mod mock_syscalls;
+pub mod privilege_scan;
+pub mod profiler;
 mod program_accounts;
 mod programs_cache;
+pub mod result;
+pub mod syscall_faults;
+mod trace;
+pub mod triage;
 
+#[cfg(feature = "debugger")]
+use protosol_entrypoints::debugger;
 use {
-    crate::fixture::{context::FixtureContext, effects::FixtureEffects, Fixture},
+    crate::{
+        fixture::{context::FixtureContext, error::FixtureError, Fixture},
+        harness::Harness,
+        mock_syscalls::MockSyscall,
+        program_accounts::ProgramFromAccounts,
+        result::{ExecutionResult, InnerInstruction, ModifiedProgram},
+        syscall_faults::SyscallFault,
+    },
     solana_program_runtime::{
         compute_budget::ComputeBudget,
         invoke_context::InvokeContext,
         loaded_programs::{LoadProgramMetrics, LoadedProgramsForTxBatch},
+        log_collector::LogCollector,
         sysvar_cache::SysvarCache,
         timings::ExecuteTimings,
     },
     solana_sdk::{
         hash::Hash,
-        instruction::AccountMeta,
-        program_error::ProgramError,
-        transaction_context::{InstructionAccount, TransactionContext},
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+        transaction_context::{InstructionAccount, TransactionContext, TransactionReturnData},
     },
-    std::{collections::HashMap, sync::Arc},
+    std::sync::Arc,
 };
+#[cfg(feature = "native")]
+use {protosol_entrypoints::native, solana_sdk::entrypoint::ProcessInstruction};
 
 /// Process a fixture using the simulated Solana program runtime.
 pub fn process_fixture(fixture: Fixture, elf: &[u8]) {
-    let Fixture { input, output } = fixture;
+    Harness::default().process_fixture(fixture, elf)
+}
+
+/// Process a fixture using the simulated Solana program runtime, loading the
+/// program from the fixture's own input accounts.
+pub fn process_fixture_from_accounts(fixture: Fixture) -> Result<(), FixtureError> {
+    Harness::default().process_fixture_from_accounts(fixture)
+}
+
+/// Process a fixture using the simulated Solana program runtime, with the
+/// program under test implemented by a native Rust `process_instruction`
+/// function.
+#[cfg(feature = "native")]
+pub fn process_fixture_native(fixture: Fixture, process_instruction: ProcessInstruction) {
+    Harness::default().process_fixture_native(fixture, process_instruction)
+}
+
+/// Process a fixture with both the SBF build and the native build of the
+/// program under test, checking that they agree.
+#[cfg(feature = "native")]
+pub fn process_fixture_differential(
+    fixture: Fixture,
+    elf: &[u8],
+    process_instruction: ProcessInstruction,
+) {
+    Harness::default().process_fixture_differential(fixture, elf, process_instruction)
+}
+
+/// Execute a fixture's instruction using the simulated Solana program
+/// runtime, loading the program from the fixture's own input accounts.
+///
+/// The loader and program status are taken from the program's accounts,
+/// which must be included in the input accounts.
+pub fn execute_instruction_from_accounts(
+    context: FixtureContext,
+) -> Result<ExecutionResult, FixtureError> {
+    let (context, elf) = context_from_accounts(context)?;
+    Ok(execute_instruction(context, &elf))
+}
+
+// Take the loader and program status from the program's accounts, returning
+// the updated context and the program's ELF.
+pub(crate) fn context_from_accounts(
+    mut context: FixtureContext,
+) -> Result<(FixtureContext, Vec<u8>), FixtureError> {
+    let ProgramFromAccounts {
+        loader_id,
+        program_status,
+        elf,
+    } = program_accounts::program_from_accounts(&context.program_id, &context.accounts)?;
+    context.loader_id = loader_id;
+    context.program_status = program_status;
+    Ok((context, elf))
+}
+
+/// Execute a fixture's instruction using the simulated Solana program
+/// runtime, returning the result rather than checking it against expected
+/// effects.
+///
+/// Builtin programs, such as `bpf_loader_upgradeable`, can be invoked
+/// directly, in which case `elf` is ignored.
+pub fn execute_instruction(context: FixtureContext, elf: &[u8]) -> ExecutionResult {
+    execute(
+        context,
+        ProgramUnderTest::Elf(elf),
+        ExecuteOptions::default(),
+    )
+    .0
+}
+
+/// Execute a fixture's instruction using the simulated Solana program
+/// runtime, with the program under test implemented by a native Rust
+/// `process_instruction` function rather than an SBF ELF.
+///
+/// The program is registered as a builtin owned by the native loader, so the
+/// fixture's loader and program status are ignored, and its program account
+/// should not be among the input accounts. Compute unit consumption does not
+/// reflect the SBF build.
+#[cfg(feature = "native")]
+pub fn execute_instruction_native(
+    context: FixtureContext,
+    process_instruction: ProcessInstruction,
+) -> ExecutionResult {
+    execute(
+        context,
+        ProgramUnderTest::Native(process_instruction),
+        ExecuteOptions::default(),
+    )
+    .0
+}
+
+/// Execute a fixture's instruction with both the SBF build and the native
+/// build of the program under test, returning both results.
+///
+/// The builds agree when the results have the same outcome, as per
+/// `ExecutionResult::same_outcome`.
+#[cfg(feature = "native")]
+pub fn execute_instruction_differential(
+    context: FixtureContext,
+    elf: &[u8],
+    process_instruction: ProcessInstruction,
+) -> (ExecutionResult, ExecutionResult) {
+    (
+        execute_instruction(context.clone(), elf),
+        execute_instruction_native(context, process_instruction),
+    )
+}
+
+// The implementation of the program under test.
+#[derive(Clone, Copy)]
+pub(crate) enum ProgramUnderTest<'a> {
+    Elf(&'a [u8]),
+    #[cfg(feature = "native")]
+    Native(ProcessInstruction),
+}
+
+// The output of an execution recorded with debugging features.
+#[derive(Default)]
+pub(crate) struct DebugOutput {
+    // The VM's instruction trace of each SBF program execution, in the order
+    // the executions finished.
+    pub(crate) traces: Vec<Vec<[u64; 12]>>,
+    // The program logs.
+    pub(crate) logs: Vec<String>,
+}
+
+// Options for executing a fixture's instruction.
+#[derive(Default)]
+pub(crate) struct ExecuteOptions<'a> {
+    // Return the VM's instruction traces and the program logs, at the cost
+    // of speed.
+    pub(crate) debugging_features: bool,
+    // Port on which each execution of the SBF program under test waits for
+    // a GDB connection.
+    #[cfg(feature = "debugger")]
+    pub(crate) debug_port: Option<u16>,
+    // Faults injected into the SBF programs' syscalls.
+    pub(crate) syscall_faults: &'a [SyscallFault],
+    // Syscalls added to, or replaced in, the program runtime environments.
+    pub(crate) mock_syscalls: &'a [MockSyscall],
+}
+
+// Execute a fixture's instruction.
+pub(crate) fn execute(
+    context: FixtureContext,
+    program: ProgramUnderTest,
+    options: ExecuteOptions,
+) -> (ExecutionResult, DebugOutput) {
+    let ExecuteOptions {
+        debugging_features,
+        syscall_faults,
+        ..
+    } = options;
+
     let FixtureContext {
         program_id,
         loader_id,
@@ -36,50 +216,115 @@ pub fn process_fixture(fixture: Fixture, elf: &[u8]) {
         accounts,
         instruction_accounts: account_metas,
         instruction_data,
-    } = input;
-    let FixtureEffects {
-        result: expected_result_code,
-        custom_error: expected_error_code,
-        modified_accounts: expected_modified_accounts,
-    } = output;
+        program_status,
+        upgrade_authority,
+        deployment_slot,
+    } = context;
 
     let compute_budget = ComputeBudget::default();
     let mut compute_units_consumed = 0;
     let mut load_program_metrics = LoadProgramMetrics::default();
-    let mut programs_modified_by_tx = LoadedProgramsForTxBatch::default();
     let rent = sysvar_context.rent.clone();
+    let slot = sysvar_context.clock.slot;
     let sysvar_cache: SysvarCache = sysvar_context.into();
     let mut timings = ExecuteTimings::default();
 
-    let program_accounts = program_accounts::program_accounts(&program_id, &loader_id, &rent, elf);
+    // Builtin programs, such as the upgradeable loader, are owned by the
+    // native loader rather than loaded from the ELF.
+    let mut program_accounts = match (
+        programs_cache::builtin_name(&program_id, &feature_set),
+        program,
+    ) {
+        (Some(name), _) => vec![program_accounts::builtin_program_account(
+            &program_id,
+            name,
+            &rent,
+        )],
+        #[cfg(feature = "native")]
+        (None, ProgramUnderTest::Native(_)) => vec![program_accounts::builtin_program_account(
+            &program_id,
+            "native_program",
+            &rent,
+        )],
+        (None, ProgramUnderTest::Elf(elf)) => program_accounts::program_accounts(
+            &program_id,
+            &loader_id,
+            program_status,
+            upgrade_authority,
+            deployment_slot.unwrap_or(0),
+            &rent,
+            elf,
+        ),
+    };
+
+    // Builtins passed to the instruction, for cross-program invocations.
+    for AccountMeta { pubkey, .. } in &account_metas {
+        if let Some(name) = programs_cache::builtin_name(pubkey, &feature_set) {
+            if !program_accounts.iter().any(|(key, _)| key == pubkey) {
+                program_accounts.push(program_accounts::builtin_program_account(
+                    pubkey, name, &rent,
+                ));
+            }
+        }
+    }
+
+    // Accounts already provided by the fixture take precedence over the
+    // synthesized program accounts.
+    program_accounts.retain(|(key, _)| !accounts.iter().any(|(account_key, _)| account_key == key));
     let program_accounts_len = program_accounts.len();
-    let program_indices = &[0];
 
-    let instruction_accounts = account_metas
-        .iter()
-        .enumerate()
-        .map(
-            |(
-                i,
-                AccountMeta {
-                    pubkey: _,
-                    is_signer,
-                    is_writable,
-                },
-            )| InstructionAccount {
-                index_in_callee: i as u16,
-                index_in_caller: i as u16,
-                index_in_transaction: (i + program_accounts_len) as u16,
-                is_signer: *is_signer,
-                is_writable: *is_writable,
-            },
-        )
-        .collect::<Vec<_>>();
+    let account_keys = accounts.iter().map(|(key, _)| *key).collect::<Vec<_>>();
 
     let transaction_accounts = program_accounts
         .into_iter()
         .chain(accounts)
         .collect::<Vec<_>>();
+    let transaction_account_keys = transaction_accounts
+        .iter()
+        .map(|(key, _)| *key)
+        .collect::<Vec<_>>();
+
+    // An account missing from the transaction fails the instruction, as the
+    // runtime would, rather than panicking.
+    let index_in_transaction = |pubkey: &Pubkey| {
+        transaction_accounts
+            .iter()
+            .position(|(key, _)| key == pubkey)
+            .map(|index| index as u16)
+            .ok_or(InstructionError::MissingAccount)
+    };
+
+    let instruction = index_in_transaction(&program_id).and_then(|program_index| {
+        let instruction_accounts = account_metas
+            .iter()
+            .enumerate()
+            .map(
+                |(
+                    i,
+                    AccountMeta {
+                        pubkey,
+                        is_signer,
+                        is_writable,
+                    },
+                )| {
+                    let index_in_transaction = index_in_transaction(pubkey)?;
+                    // Duplicate instruction accounts refer to the first occurrence.
+                    let index_in_callee = account_metas[..i]
+                        .iter()
+                        .position(|meta| meta.pubkey == *pubkey)
+                        .unwrap_or(i) as u16;
+                    Ok(InstructionAccount {
+                        index_in_callee,
+                        index_in_caller: index_in_transaction,
+                        index_in_transaction,
+                        is_signer: *is_signer,
+                        is_writable: *is_writable,
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>, InstructionError>>()?;
+        Ok((program_index, instruction_accounts))
+    });
 
     let mut transaction_context = TransactionContext::new(
         transaction_accounts,
@@ -88,19 +333,51 @@ pub fn process_fixture(fixture: Fixture, elf: &[u8]) {
         compute_budget.max_instruction_trace_length,
     );
 
-    let loaded_programs_cache = programs_cache::build_loaded_programs_cache(
-        &program_id,
-        &loader_id,
-        &compute_budget,
-        &feature_set,
-        &mut load_program_metrics,
-        elf,
+    // The loaders' entrypoints are replaced when debugging.
+    #[cfg_attr(not(feature = "debugger"), allow(unused_mut))]
+    let (mut loaded_programs_cache, program_load_error) = match program {
+        ProgramUnderTest::Elf(elf) => programs_cache::build_loaded_programs_cache(
+            &program_id,
+            &loader_id,
+            program_status,
+            deployment_slot,
+            slot,
+            &compute_budget,
+            &feature_set,
+            &mut load_program_metrics,
+            elf,
+            &options,
+        ),
+        #[cfg(feature = "native")]
+        ProgramUnderTest::Native(_) => programs_cache::build_native_programs_cache(
+            &program_id,
+            native::Entrypoint::vm,
+            slot,
+            &compute_budget,
+            &feature_set,
+        ),
+    };
+
+    #[cfg(feature = "debugger")]
+    if options.debug_port.is_some() {
+        programs_cache::replace_loader_entrypoints(
+            &mut loaded_programs_cache,
+            debugger::LoaderEntrypoint::vm,
+            &feature_set,
+        );
+    }
+
+    let mut programs_modified_by_tx = LoadedProgramsForTxBatch::new(
+        loaded_programs_cache.slot(),
+        loaded_programs_cache.environments.clone(),
     );
 
+    let log_collector = debugging_features.then(|| LogCollector::new_ref_with_limit(None));
+
     let mut invoke_context = InvokeContext::new(
         &mut transaction_context,
         &sysvar_cache,
-        None,
+        log_collector.clone(),
         compute_budget,
         &loaded_programs_cache,
         &mut programs_modified_by_tx,
@@ -109,39 +386,75 @@ pub fn process_fixture(fixture: Fixture, elf: &[u8]) {
         0,
     );
 
-    let result = invoke_context.process_instruction(
-        &instruction_data,
-        &instruction_accounts,
-        program_indices,
-        &mut compute_units_consumed,
-        &mut timings,
-    );
-
-    let (result_code, error_code): (i32, u64) = match result {
-        Ok(()) => (0, 0),
-        Err(err) => {
-            if let Ok(program_err) = ProgramError::try_from(err) {
-                (-1, u64::from(program_err))
-            } else {
-                (-1, u64::MAX)
+    let mut process_instruction = || {
+        let (program_index, instruction_accounts) = instruction.clone()?;
+        invoke_context.process_instruction(
+            &instruction_data,
+            &instruction_accounts,
+            &[program_index],
+            &mut compute_units_consumed,
+            &mut timings,
+        )
+    };
+    let result = match program {
+        ProgramUnderTest::Elf(_) => syscall_faults::with_syscall_faults(syscall_faults, || {
+            #[cfg(feature = "debugger")]
+            if let Some(debug_port) = options.debug_port {
+                return debugger::with_debug_port(program_id, debug_port, process_instruction);
             }
+            process_instruction()
+        }),
+        #[cfg(feature = "native")]
+        ProgramUnderTest::Native(native_process_instruction) => {
+            native::with_processor(native_process_instruction, process_instruction)
         }
     };
 
+    let debug_output = DebugOutput {
+        traces: invoke_context.get_traces().clone(),
+        logs: log_collector
+            .map(|log_collector| log_collector.borrow().get_recorded_content().to_vec())
+            .unwrap_or_default(),
+    };
+
+    // Programs deployed, upgraded or closed by the instruction.
+    let modified_programs = transaction_account_keys
+        .iter()
+        .filter_map(|key| {
+            programs_modified_by_tx
+                .find(key)
+                .and_then(|entry| ModifiedProgram::from_cache_entry(*key, &entry))
+        })
+        .collect::<Vec<_>>();
+
+    let inner_instructions = InnerInstruction::from_instruction_trace(&transaction_context);
+
+    // As in the runtime, empty return data means no data was returned.
+    let (return_data_program_id, return_data) = transaction_context.get_return_data();
+    let return_data = (!return_data.is_empty()).then(|| TransactionReturnData {
+        program_id: *return_data_program_id,
+        data: return_data.to_vec(),
+    });
+
     let resulting_accounts = transaction_context
         .deconstruct_without_keys()
         .unwrap()
         .into_iter()
         .skip(program_accounts_len)
-        .zip(account_metas.iter().map(|meta| meta.pubkey))
+        .zip(account_keys)
         .map(|(account, key)| (key, account))
-        .collect::<HashMap<_, _>>();
+        .collect::<Vec<_>>();
 
-    assert_eq!(result_code, expected_result_code);
-    assert_eq!(error_code, expected_error_code);
-    for (key, expected_modified_account) in expected_modified_accounts {
-        if let Some(account) = resulting_accounts.get(&key) {
-            assert_eq!(account, &expected_modified_account);
-        }
-    }
+    let execution_result = ExecutionResult {
+        result,
+        compute_units_consumed,
+        resulting_accounts,
+        program_load_error,
+        modified_programs,
+        return_data,
+        inner_instructions,
+        backtrace: None,
+    };
+
+    (execution_result, debug_output)
 }
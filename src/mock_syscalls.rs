@@ -0,0 +1,162 @@
+//! Custom mock syscalls.
+//!
+//! Programs targeting forks or newer runtimes may call syscalls which the
+//! program runtime environment lacks, and tests may need deterministic stubs
+//! for syscalls such as `sol_remaining_compute_units`. Mock syscalls add to,
+//! or override, the syscalls of the environments the harness creates.
+//!
+//! Mocks are builtin functions, declared as the runtime's own syscalls are:
+//!
+//! ```
+//! use {
+//!     protosol::{harness::Harness, mock_syscalls::MockSyscall},
+//!     solana_program_runtime::{
+//!         invoke_context::InvokeContext,
+//!         solana_rbpf::{declare_builtin_function, memory_region::MemoryMapping},
+//!     },
+//! };
+//!
+//! declare_builtin_function!(
+//!     SyscallGetEpochStake,
+//!     fn rust(
+//!         _invoke_context: &mut InvokeContext,
+//!         _vote_address: u64,
+//!         _arg_b: u64,
+//!         _arg_c: u64,
+//!         _arg_d: u64,
+//!         _arg_e: u64,
+//!         _memory_mapping: &mut MemoryMapping,
+//!     ) -> Result<u64, Box<dyn std::error::Error>> {
+//!         Ok(42)
+//!     }
+//! );
+//!
+//! let harness = Harness::default().with_mock_syscall(MockSyscall::new(
+//!     "sol_get_epoch_stake",
+//!     SyscallGetEpochStake::vm,
+//! ));
+//! ```
+
+use {
+    solana_program_runtime::{
+        invoke_context::InvokeContext,
+        solana_rbpf::{
+            elf::ElfError,
+            program::{BuiltinFunction, BuiltinProgram, FunctionRegistry},
+        },
+    },
+    std::fmt,
+};
+
+/// A syscall's builtin function, such as the `vm` function of a type
+/// declared by `declare_builtin_function!`.
+pub type SyscallFunction = BuiltinFunction<InvokeContext<'static>>;
+
+/// A mock syscall, replacing any syscall of the same name.
+#[derive(Clone)]
+pub struct MockSyscall {
+    /// The name of the syscall, such as `sol_get_epoch_stake`.
+    pub name: String,
+    /// The builtin function implementing the syscall.
+    pub function: SyscallFunction,
+}
+
+impl MockSyscall {
+    /// Create a mock syscall.
+    pub fn new(name: &str, function: SyscallFunction) -> Self {
+        Self {
+            name: name.to_string(),
+            function,
+        }
+    }
+}
+
+impl fmt::Debug for MockSyscall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockSyscall")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Add the mock syscalls to a program runtime environment, replacing any of
+/// the same name.
+pub(crate) fn register_mock_syscalls(
+    environment: &BuiltinProgram<InvokeContext<'static>>,
+    mock_syscalls: &[MockSyscall],
+) -> Result<BuiltinProgram<InvokeContext<'static>>, ElfError> {
+    let mut functions = FunctionRegistry::default();
+    for (_, (name, function)) in environment.get_function_registry().iter() {
+        if !mock_syscalls
+            .iter()
+            .any(|mock_syscall| mock_syscall.name.as_bytes() == name)
+        {
+            functions.register_function_hashed(name, function)?;
+        }
+    }
+    for MockSyscall { name, function } in mock_syscalls {
+        functions.register_function_hashed(name.as_bytes(), *function)?;
+    }
+    Ok(BuiltinProgram::new_loader(
+        *environment.get_config(),
+        functions,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_bpf_loader_program::syscalls::{
+            create_program_runtime_environment_v1, SyscallAbort,
+        },
+        solana_program_runtime::compute_budget::ComputeBudget,
+        solana_sdk::feature_set::FeatureSet,
+    };
+
+    #[test]
+    fn test_register_mock_syscalls() {
+        let environment = create_program_runtime_environment_v1(
+            &FeatureSet::all_enabled(),
+            &ComputeBudget::default(),
+            false,
+            false,
+        )
+        .unwrap();
+        let registry = environment.get_function_registry();
+        assert!(registry.lookup_by_name(b"sol_get_epoch_stake").is_none());
+
+        let mocked = register_mock_syscalls(
+            &environment,
+            &[
+                MockSyscall::new("sol_get_epoch_stake", SyscallAbort::vm),
+                MockSyscall::new("sol_remaining_compute_units", SyscallAbort::vm),
+            ],
+        )
+        .unwrap();
+        let mocked_registry = mocked.get_function_registry();
+
+        // Function pointers are compared by address.
+        let address = |registry: &FunctionRegistry<SyscallFunction>, name: &[u8]| {
+            registry
+                .lookup_by_name(name)
+                .map(|(_, function)| function as usize)
+        };
+        let abort = Some(SyscallAbort::vm as SyscallFunction as usize);
+        // Added.
+        assert_eq!(address(mocked_registry, b"sol_get_epoch_stake"), abort);
+        // Replaced.
+        assert_eq!(
+            address(mocked_registry, b"sol_remaining_compute_units"),
+            abort
+        );
+        // Kept.
+        assert_eq!(
+            address(mocked_registry, b"sol_log_"),
+            address(registry, b"sol_log_")
+        );
+        assert_ne!(address(mocked_registry, b"sol_log_"), abort);
+        assert_eq!(mocked_registry.iter().count(), registry.iter().count() + 1);
+        assert_eq!(mocked.get_config(), environment.get_config());
+    }
+}
@@ -0,0 +1,214 @@
+//! Privilege-escalation scanning, for detecting missing signer and writable
+//! checks.
+//!
+//! A passing fixture is executed again once for each signer or writable
+//! instruction account with the privilege stripped. A variant which still
+//! succeeds with the same outcome indicates the program never required the
+//! privilege, such as a missing authority check.
+
+use {
+    crate::{
+        fixture::{context::FixtureContext, error::FixtureError},
+        harness::Harness,
+        result::ExecutionResult,
+    },
+    solana_sdk::pubkey::Pubkey,
+};
+
+/// A privilege of an instruction account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Privilege {
+    /// The account signed the transaction.
+    Signer,
+    /// The account is writable.
+    Writable,
+}
+
+/// A variant of a fixture's instruction accounts, with one privilege
+/// stripped from one account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivilegeVariant {
+    /// The index of the account's first instruction account.
+    pub instruction_account_index: usize,
+    /// The account's public key.
+    pub pubkey: Pubkey,
+    /// The privilege stripped from the account.
+    pub privilege: Privilege,
+}
+
+impl PrivilegeVariant {
+    // Strip the privilege from every instruction account of the account, as
+    // the runtime grants an account the privileges of any of its duplicates.
+    fn apply(&self, context: &mut FixtureContext) {
+        context
+            .instruction_accounts
+            .iter_mut()
+            .filter(|meta| meta.pubkey == self.pubkey)
+            .for_each(|meta| match self.privilege {
+                Privilege::Signer => meta.is_signer = false,
+                Privilege::Writable => meta.is_writable = false,
+            });
+    }
+}
+
+/// A variant which succeeds with the same outcome as the fixture, despite
+/// the stripped privilege.
+#[derive(Debug)]
+pub struct PrivilegeEscalation {
+    /// The privilege variant.
+    pub variant: PrivilegeVariant,
+    /// The result of executing the fixture with the variant.
+    pub result: ExecutionResult,
+}
+
+/// The report of a privilege-escalation scan.
+#[derive(Debug)]
+pub struct PrivilegeScanReport {
+    /// The result of executing the fixture with its own instruction
+    /// accounts.
+    pub baseline: ExecutionResult,
+    /// The number of variants executed, excluding the baseline.
+    pub variants_executed: usize,
+    /// The variants whose outcome matches the baseline.
+    pub escalations: Vec<PrivilegeEscalation>,
+}
+
+fn privilege_variants(context: &FixtureContext) -> Vec<PrivilegeVariant> {
+    let mut variants = Vec::new();
+    for (index, meta) in context.instruction_accounts.iter().enumerate() {
+        // Duplicates are covered by the account's first instruction account.
+        if context.instruction_accounts[..index]
+            .iter()
+            .any(|previous| previous.pubkey == meta.pubkey)
+        {
+            continue;
+        }
+        let duplicates = || {
+            context.instruction_accounts[index..]
+                .iter()
+                .filter(|duplicate| duplicate.pubkey == meta.pubkey)
+        };
+        for (privilege, granted) in [
+            (Privilege::Signer, duplicates().any(|meta| meta.is_signer)),
+            (
+                Privilege::Writable,
+                duplicates().any(|meta| meta.is_writable),
+            ),
+        ] {
+            if granted {
+                variants.push(PrivilegeVariant {
+                    instruction_account_index: index,
+                    pubkey: meta.pubkey,
+                    privilege,
+                });
+            }
+        }
+    }
+    variants
+}
+
+/// Execute a passing fixture's instruction with the harness, with its own
+/// instruction accounts, then once for each signer and writable account with
+/// the privilege stripped, and report the variants which still succeed with
+/// the same outcome.
+///
+/// Fails with `FixtureError::BaselineFailed` if the fixture's own
+/// instruction fails.
+pub fn run_privilege_scan(
+    harness: &Harness,
+    context: &FixtureContext,
+    elf: &[u8],
+) -> Result<PrivilegeScanReport, FixtureError> {
+    let variants = privilege_variants(context);
+    let variants_executed = variants.len();
+    let (baseline, escalations) = harness.scan_variants(
+        context,
+        elf,
+        variants,
+        PrivilegeVariant::apply,
+        ExecutionResult::same_outcome,
+    )?;
+
+    Ok(PrivilegeScanReport {
+        baseline,
+        variants_executed,
+        escalations: escalations
+            .into_iter()
+            .map(|(variant, result)| PrivilegeEscalation { variant, result })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::fixture::{context::ProgramStatus, sysvars::FixtureSysvarContext},
+        solana_sdk::{
+            account::AccountSharedData, bpf_loader_upgradeable, feature_set::FeatureSet,
+            instruction::AccountMeta,
+        },
+    };
+
+    #[test]
+    fn test_privilege_variants() {
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+
+        let mut context = FixtureContext {
+            accounts: vec![
+                (authority, AccountSharedData::default()),
+                (destination, AccountSharedData::default()),
+            ],
+            instruction_accounts: vec![
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(program, false),
+                AccountMeta::new(authority, false),
+            ],
+            program_id: program,
+            loader_id: bpf_loader_upgradeable::id(),
+            feature_set: FeatureSet::all_enabled(),
+            sysvar_context: FixtureSysvarContext::default(),
+            instruction_data: vec![],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
+        };
+
+        let variants = privilege_variants(&context);
+        assert_eq!(
+            variants,
+            vec![
+                PrivilegeVariant {
+                    instruction_account_index: 0,
+                    pubkey: authority,
+                    privilege: Privilege::Signer,
+                },
+                PrivilegeVariant {
+                    instruction_account_index: 0,
+                    pubkey: authority,
+                    privilege: Privilege::Writable,
+                },
+                PrivilegeVariant {
+                    instruction_account_index: 1,
+                    pubkey: destination,
+                    privilege: Privilege::Writable,
+                },
+            ]
+        );
+
+        // Duplicates are stripped too.
+        variants[1].apply(&mut context);
+        assert_eq!(
+            context.instruction_accounts,
+            vec![
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(program, false),
+                AccountMeta::new_readonly(authority, false),
+            ]
+        );
+    }
+}
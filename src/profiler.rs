@@ -0,0 +1,369 @@
+//! Compute unit profiling of SBF programs, attributing the compute units
+//! consumed to the program's functions, for flamegraphs.
+//!
+//! Every instruction executed by the VM consumes one compute unit, so the
+//! instruction trace of each program execution is folded into call stacks.
+//! Function frames are found from the frame pointer, which moves on every
+//! call and return, and named from the ELF's symbol table. Cross-program
+//! invocations are shown as separate frames, below the caller's stack at the
+//! point of the invocation.
+
+use {
+    crate::trace::{walk_frames, INSN_SIZE},
+    addr2line::object::{
+        File, Object, ObjectSection, ObjectSymbol, ObjectSymbolTable, RelocationTarget, SymbolKind,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::{BTreeMap, HashSet},
+        io::Write,
+    },
+    thiserror::Error,
+};
+
+// The opcode of a call to a function or syscall by immediate.
+const CALL_IMM_OPCODE: u8 = 0x85;
+
+// The syscalls which invoke other programs.
+const CPI_SYSCALLS: &[&str] = &["sol_invoke_signed_c", "sol_invoke_signed_rust"];
+
+// The frame of compute units consumed outside of the traced programs, by
+// syscalls and builtin programs.
+const UNTRACED_FRAME: &str = "[syscalls and builtins]";
+
+/// Errors possible for profiling a program.
+#[derive(Debug, Error, PartialEq)]
+pub enum ProfilerError {
+    /// The ELF could not be parsed.
+    #[error("Invalid ELF: {0}")]
+    InvalidElf(String),
+    /// The ELF has no `.text` section.
+    #[error("Missing text section")]
+    MissingTextSection,
+}
+
+/// The compute units consumed by each call stack of an SBF program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    /// Compute units consumed, keyed by call stack, with frames separated by
+    /// semicolons from the outermost.
+    pub stacks: BTreeMap<String, u64>,
+}
+
+impl Profile {
+    /// Merge the compute units of another profile into this one.
+    pub fn merge(&mut self, other: &Profile) {
+        for (stack, compute_units) in &other.stacks {
+            *self.stacks.entry(stack.clone()).or_default() += compute_units;
+        }
+    }
+
+    /// Write the profile as folded stacks, for tools such as `inferno` or
+    /// `flamegraph.pl`.
+    pub fn write_folded(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for (stack, compute_units) in &self.stacks {
+            writeln!(writer, "{} {}", stack, compute_units)?;
+        }
+        Ok(())
+    }
+}
+
+// The function symbols and invocation syscalls of a program's ELF.
+pub(crate) struct ElfSymbols {
+    // Functions by start program counter, with their end and name.
+    functions: BTreeMap<u64, (u64, String)>,
+    // Program counters of calls to cross-program invocation syscalls.
+    cpi_calls: HashSet<u64>,
+}
+
+impl ElfSymbols {
+    pub(crate) fn parse(elf: &[u8]) -> Result<Self, ProfilerError> {
+        let file =
+            File::parse(elf).map_err(|error| ProfilerError::InvalidElf(error.to_string()))?;
+        let text_section = file
+            .section_by_name(".text")
+            .ok_or(ProfilerError::MissingTextSection)?;
+        let text_address = text_section.address();
+        let text = text_section
+            .data()
+            .map_err(|error| ProfilerError::InvalidElf(error.to_string()))?;
+        let to_pc = |address: u64| address.checked_sub(text_address).map(|x| x / INSN_SIZE);
+
+        let functions = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.size() > 0)
+            .filter_map(|symbol| {
+                let start = to_pc(symbol.address())?;
+                let end = start.saturating_add(symbol.size() / INSN_SIZE);
+                let name = symbol.name().ok()?;
+                Some((start, (end, demangle(name))))
+            })
+            .collect();
+
+        // Syscalls are resolved by relocations, at load time.
+        let cpi_calls = match (file.dynamic_relocations(), file.dynamic_symbol_table()) {
+            (Some(relocations), Some(symbol_table)) => relocations
+                .filter_map(|(offset, relocation)| {
+                    let RelocationTarget::Symbol(index) = relocation.target() else {
+                        return None;
+                    };
+                    let name = symbol_table.symbol_by_index(index).ok()?.name().ok()?;
+                    let pc = to_pc(offset)?;
+                    let opcode = text.get((pc * INSN_SIZE) as usize)?;
+                    (*opcode == CALL_IMM_OPCODE && CPI_SYSCALLS.contains(&name)).then_some(pc)
+                })
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        Ok(Self {
+            functions,
+            cpi_calls,
+        })
+    }
+
+    pub(crate) fn function_name(&self, pc: u64) -> String {
+        match self.functions.range(..=pc).next_back() {
+            Some((_, (end, name))) if pc < *end => name.clone(),
+            _ => format!("function_{:#x}", pc),
+        }
+    }
+}
+
+// Folded stacks use semicolons to separate frames.
+pub(crate) fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name)).replace(';', ",")
+}
+
+// The order in which invocations finished, given their stack heights in the
+// order they started.
+fn finish_order(stack_heights: &[u32]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(stack_heights.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (index, stack_height) in stack_heights.iter().enumerate() {
+        while let Some(top) = stack.last() {
+            if stack_heights[*top] < *stack_height {
+                break;
+            }
+            order.push(stack.pop().unwrap());
+        }
+        stack.push(index);
+    }
+    order.extend(stack.into_iter().rev());
+    order
+}
+
+// Fold one program execution's trace into the profile, returning the call
+// stacks at each cross-program invocation, in order.
+fn fold_trace(
+    symbols: &ElfSymbols,
+    prefix: &str,
+    trace: &[[u64; 12]],
+    profile: &mut Profile,
+) -> Vec<String> {
+    let mut cpi_stacks = Vec::new();
+    walk_frames(
+        trace,
+        |pc| symbols.function_name(pc),
+        |frames, pc| {
+            let stack = frames.iter().fold(prefix.to_string(), |stack, name| {
+                format!("{};{}", stack, name)
+            });
+            if symbols.cpi_calls.contains(&pc) {
+                cpi_stacks.push(stack.clone());
+            }
+            *profile.stacks.entry(stack).or_default() += 1;
+        },
+    );
+    cpi_stacks
+}
+
+// Profile one execution of an instruction, given the program ID and stack
+// height of every invocation in the order they started, starting with the
+// top-level instruction, and the instruction traces of the executions of the
+// profiled program, in the order they finished.
+pub(crate) fn profile_execution(
+    symbols: &ElfSymbols,
+    program_id: &Pubkey,
+    invocations: &[(Pubkey, u32)],
+    traces: &[Vec<[u64; 12]>],
+    compute_units_consumed: u64,
+) -> Profile {
+    let mut profile = Profile::default();
+    let Some((root_program_id, _)) = invocations.first() else {
+        return profile;
+    };
+    let root = root_program_id.to_string();
+
+    // Match each trace to its invocation.
+    let stack_heights = invocations
+        .iter()
+        .map(|(_, stack_height)| *stack_height)
+        .collect::<Vec<_>>();
+    let traced = finish_order(&stack_heights)
+        .into_iter()
+        .filter(|index| invocations[*index].0 == *program_id)
+        .collect::<Vec<_>>();
+    let mut invocation_traces = vec![None; invocations.len()];
+    if traced.len() == traces.len() {
+        for (index, trace) in traced.into_iter().zip(traces) {
+            invocation_traces[index] = Some(trace);
+        }
+    } else {
+        // Without a match, the traces can't be nested.
+        for trace in traces {
+            fold_trace(symbols, &root, trace, &mut profile);
+        }
+    }
+
+    // Fold each invocation below its caller's stack at the point of the
+    // invocation.
+    let mut prefixes = vec![root.clone(); invocations.len()];
+    for index in 0..invocations.len() {
+        let cpi_stacks = match invocation_traces[index] {
+            Some(trace) => fold_trace(symbols, &prefixes[index], trace, &mut profile),
+            None => vec![],
+        };
+        let children = (index + 1..invocations.len())
+            .take_while(|child| stack_heights[*child] > stack_heights[index])
+            .filter(|child| stack_heights[*child] == stack_heights[index] + 1);
+        for (position, child) in children.enumerate() {
+            let caller_stack = cpi_stacks.get(position).unwrap_or(&prefixes[index]).clone();
+            prefixes[child] = format!("{};[CPI] {}", caller_stack, invocations[child].0);
+        }
+    }
+
+    let traced_compute_units = profile.stacks.values().sum::<u64>();
+    let untraced_compute_units = compute_units_consumed.saturating_sub(traced_compute_units);
+    if untraced_compute_units > 0 {
+        *profile
+            .stacks
+            .entry(format!("{};{}", root, UNTRACED_FRAME))
+            .or_default() += untraced_compute_units;
+    }
+
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::trace::{FRAME_POINTER_INDEX, PC_INDEX},
+    };
+
+    fn trace_entry(frame_pointer: u64, pc: u64) -> [u64; 12] {
+        let mut state = [0; 12];
+        state[FRAME_POINTER_INDEX] = frame_pointer;
+        state[PC_INDEX] = pc;
+        state
+    }
+
+    fn symbols() -> ElfSymbols {
+        ElfSymbols {
+            functions: BTreeMap::from([
+                (0, (10, String::from("entrypoint"))),
+                (10, (20, String::from("process"))),
+            ]),
+            cpi_calls: HashSet::from([12]),
+        }
+    }
+
+    #[test]
+    fn test_finish_order() {
+        assert_eq!(finish_order(&[]), Vec::<usize>::new());
+        assert_eq!(finish_order(&[1]), vec![0]);
+        assert_eq!(finish_order(&[1, 2, 2, 3, 2]), vec![1, 3, 2, 4, 0]);
+    }
+
+    #[test]
+    fn test_function_name() {
+        let symbols = symbols();
+        assert_eq!(symbols.function_name(0), "entrypoint");
+        assert_eq!(symbols.function_name(15), "process");
+        assert_eq!(symbols.function_name(20), "function_0x14");
+    }
+
+    #[test]
+    fn test_profile_execution() {
+        let program_id = Pubkey::new_unique();
+        let callee_id = Pubkey::new_unique();
+
+        // The entrypoint calls `process`, which invokes the callee before
+        // returning.
+        let trace = vec![
+            trace_entry(100, 0),
+            trace_entry(100, 1),
+            trace_entry(200, 10),
+            trace_entry(200, 11),
+            trace_entry(200, 12),
+            trace_entry(100, 2),
+        ];
+        let profile = profile_execution(
+            &symbols(),
+            &program_id,
+            &[(program_id, 1), (callee_id, 2)],
+            &[trace],
+            10,
+        );
+
+        let root = program_id.to_string();
+        assert_eq!(
+            profile.stacks,
+            BTreeMap::from([
+                (format!("{};entrypoint", root), 3),
+                (format!("{};entrypoint;process", root), 3),
+                (format!("{};[syscalls and builtins]", root), 4),
+            ])
+        );
+
+        // The program invokes itself.
+        let callee_trace = vec![trace_entry(100, 0)];
+        let caller_trace = vec![
+            trace_entry(100, 0),
+            trace_entry(200, 12),
+            trace_entry(100, 1),
+        ];
+        let profile = profile_execution(
+            &symbols(),
+            &program_id,
+            &[(program_id, 1), (program_id, 2)],
+            &[callee_trace, caller_trace],
+            4,
+        );
+        assert_eq!(
+            profile.stacks,
+            BTreeMap::from([
+                (format!("{};entrypoint", root), 2),
+                (format!("{};entrypoint;process", root), 1),
+                (
+                    format!("{};entrypoint;process;[CPI] {};entrypoint", root, root),
+                    1
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_write_folded() {
+        let mut profile = Profile {
+            stacks: BTreeMap::from([(String::from("a;b"), 2)]),
+        };
+        profile.merge(&Profile {
+            stacks: BTreeMap::from([(String::from("a;b"), 3), (String::from("a"), 1)]),
+        });
+
+        let mut folded = Vec::new();
+        profile.write_folded(&mut folded).unwrap();
+        assert_eq!(String::from_utf8(folded).unwrap(), "a 1\na;b 5\n");
+    }
+
+    #[test]
+    fn test_parse_invalid_elf() {
+        assert!(matches!(
+            ElfSymbols::parse(&[1, 2, 3, 4]),
+            Err(ProfilerError::InvalidElf(_))
+        ));
+    }
+}
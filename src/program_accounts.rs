@@ -1,16 +1,38 @@
 //! Program accounts.
 
-use solana_sdk::{
-    account::{Account, AccountSharedData},
-    bpf_loader_upgradeable::UpgradeableLoaderState,
-    pubkey::Pubkey,
-    rent::Rent,
+use {
+    crate::fixture::{context::ProgramStatus, error::FixtureError},
+    solana_sdk::{
+        account::{Account, AccountSharedData, ReadableAccount},
+        bpf_loader_upgradeable::UpgradeableLoaderState,
+        loader_v4::{LoaderV4State, LoaderV4Status},
+        pubkey::Pubkey,
+        rent::Rent,
+    },
 };
 
+// `LoaderV4State` is `repr(C)` and not serializable, so its layout is
+// written out field by field.
+fn loader_v4_state_bytes(state: &LoaderV4State) -> Vec<u8> {
+    let mut data = Vec::with_capacity(LoaderV4State::program_data_offset());
+    data.extend_from_slice(&state.slot.to_le_bytes());
+    data.extend_from_slice(state.authority_address.as_ref());
+    data.extend_from_slice(&(state.status as u64).to_le_bytes());
+    data
+}
+
 /// Create the program accounts for a given program.
+///
+/// Programs owned by `bpf_loader_upgradeable` get a program account and a
+/// program data account, and programs owned by loader v4 get a single
+/// account with a state header. Programs owned by `bpf_loader` or
+/// `bpf_loader_deprecated` get a single account holding the raw ELF.
 pub fn program_accounts(
     program_id: &Pubkey,
     loader_id: &Pubkey,
+    program_status: ProgramStatus,
+    upgrade_authority: Option<Pubkey>,
+    deployment_slot: u64,
     rent: &Rent,
     elf: &[u8],
 ) -> Vec<(Pubkey, AccountSharedData)> {
@@ -20,17 +42,30 @@ pub fn program_accounts(
         let (programdata_address, _) =
             Pubkey::find_program_address(&[program_id.as_ref()], loader_id);
 
-        let mut program_data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
-            slot: 0,
-            upgrade_authority_address: Some(Pubkey::default()),
-        })
-        .unwrap();
-        program_data.extend_from_slice(elf);
+        // Closing a program empties its program data account, but leaves the
+        // program account in place.
+        let program_data = if program_status == ProgramStatus::Closed {
+            bincode::serialize(&UpgradeableLoaderState::Uninitialized).unwrap()
+        } else {
+            let mut program_data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: deployment_slot,
+                upgrade_authority_address: upgrade_authority,
+            })
+            .unwrap();
+            // The metadata of immutable programs is shorter when serialized.
+            program_data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+            program_data.extend_from_slice(elf);
+            program_data
+        };
 
         accounts.push((
             programdata_address,
             AccountSharedData::from(Account {
-                lamports: rent.minimum_balance(program_data.len()).max(1),
+                lamports: if program_status == ProgramStatus::Closed {
+                    0
+                } else {
+                    rent.minimum_balance(program_data.len()).max(1)
+                },
                 data: program_data,
                 owner: *loader_id,
                 executable: false,
@@ -42,6 +77,25 @@ pub fn program_accounts(
             programdata_address,
         })
         .unwrap()
+    } else if *loader_id == solana_sdk::loader_v4::id() {
+        let status = match program_status {
+            ProgramStatus::Deployed => Some(LoaderV4Status::Deployed),
+            ProgramStatus::Retracted => Some(LoaderV4Status::Retracted),
+            ProgramStatus::Finalized => Some(LoaderV4Status::Finalized),
+            // Closing a program truncates its account to zero length.
+            ProgramStatus::Closed => None,
+        };
+        status
+            .map(|status| {
+                let mut data = loader_v4_state_bytes(&LoaderV4State {
+                    slot: deployment_slot,
+                    authority_address: upgrade_authority.unwrap_or_default(),
+                    status,
+                });
+                data.extend_from_slice(elf);
+                data
+            })
+            .unwrap_or_default()
     } else {
         elf.to_vec()
     };
@@ -61,9 +115,118 @@ pub fn program_accounts(
     accounts
 }
 
+/// Create the account of a builtin program, owned by the native loader.
+pub fn builtin_program_account(
+    program_id: &Pubkey,
+    name: &str,
+    rent: &Rent,
+) -> (Pubkey, AccountSharedData) {
+    (
+        *program_id,
+        AccountSharedData::from(Account {
+            lamports: rent.minimum_balance(name.len()).max(1),
+            data: name.as_bytes().to_vec(),
+            owner: solana_sdk::native_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        }),
+    )
+}
+
+/// A program extracted from a fixture's accounts.
+#[derive(Debug, PartialEq)]
+pub struct ProgramFromAccounts {
+    /// The loader which owns the program.
+    pub loader_id: Pubkey,
+    /// The deployment status of the program.
+    pub program_status: ProgramStatus,
+    /// The program ELF. Empty for closed programs.
+    pub elf: Vec<u8>,
+}
+
+fn loader_v4_program_status(data: &[u8]) -> Result<ProgramStatus, FixtureError> {
+    let status = data
+        .get(LoaderV4State::program_data_offset() - 8..LoaderV4State::program_data_offset())
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(FixtureError::InvalidProgramAccount)?;
+    match status {
+        0 => Ok(ProgramStatus::Retracted),
+        1 => Ok(ProgramStatus::Deployed),
+        2 => Ok(ProgramStatus::Finalized),
+        _ => Err(FixtureError::InvalidProgramAccount),
+    }
+}
+
+/// Extract a program from its accounts, as included in a fixture's input
+/// accounts, for any supported loader.
+pub fn program_from_accounts(
+    program_id: &Pubkey,
+    accounts: &[(Pubkey, AccountSharedData)],
+) -> Result<ProgramFromAccounts, FixtureError> {
+    let find_account = |address: &Pubkey| {
+        accounts
+            .iter()
+            .find(|(key, _)| key == address)
+            .map(|(_, account)| account)
+            .ok_or(FixtureError::InvalidProgramAccount)
+    };
+
+    let program_account = find_account(program_id)?;
+    let loader_id = *program_account.owner();
+    let data = program_account.data();
+
+    let (program_status, elf) = if loader_id == solana_sdk::bpf_loader_upgradeable::id() {
+        let programdata_address = match bincode::deserialize(data) {
+            Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) => programdata_address,
+            _ => return Err(FixtureError::InvalidProgramAccount),
+        };
+        let programdata_account = find_account(&programdata_address)?;
+        match bincode::deserialize(programdata_account.data()) {
+            Ok(UpgradeableLoaderState::ProgramData { .. })
+                if programdata_account.lamports() > 0 =>
+            {
+                // Metadata without an upgrade authority serializes shorter
+                // than its padded size.
+                let elf = programdata_account
+                    .data()
+                    .get(UpgradeableLoaderState::size_of_programdata_metadata()..)
+                    .ok_or(FixtureError::InvalidProgramAccount)?;
+                (ProgramStatus::Deployed, elf.to_vec())
+            }
+            Ok(UpgradeableLoaderState::Uninitialized)
+            | Ok(UpgradeableLoaderState::ProgramData { .. }) => (ProgramStatus::Closed, vec![]),
+            _ => return Err(FixtureError::InvalidProgramAccount),
+        }
+    } else if loader_id == solana_sdk::loader_v4::id() {
+        if data.is_empty() {
+            (ProgramStatus::Closed, vec![])
+        } else {
+            (
+                loader_v4_program_status(data)?,
+                data[LoaderV4State::program_data_offset()..].to_vec(),
+            )
+        }
+    } else if loader_id == solana_sdk::bpf_loader::id()
+        || loader_id == solana_sdk::bpf_loader_deprecated::id()
+    {
+        (ProgramStatus::Deployed, data.to_vec())
+    } else {
+        return Err(FixtureError::InvalidProgramAccount);
+    };
+
+    Ok(ProgramFromAccounts {
+        loader_id,
+        program_status,
+        elf,
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {super::*, solana_sdk::account::WritableAccount};
 
     #[test]
     fn test_program_accounts() {
@@ -75,7 +238,15 @@ mod tests {
 
         let loader_id = solana_sdk::bpf_loader::id();
 
-        let accounts = program_accounts(&program_id, &loader_id, &rent, &elf);
+        let accounts = program_accounts(
+            &program_id,
+            &loader_id,
+            ProgramStatus::default(),
+            None,
+            0,
+            &rent,
+            &elf,
+        );
         assert_eq!(accounts.len(), 1);
 
         let (result_program_address, result_program_account) = &accounts[0];
@@ -91,6 +262,33 @@ mod tests {
             })
         );
 
+        // Deprecated loader
+
+        let loader_id = solana_sdk::bpf_loader_deprecated::id();
+
+        let accounts = program_accounts(
+            &program_id,
+            &loader_id,
+            ProgramStatus::default(),
+            None,
+            0,
+            &rent,
+            &elf,
+        );
+        assert_eq!(
+            accounts,
+            vec![(
+                program_id,
+                AccountSharedData::from(Account {
+                    lamports: rent.minimum_balance(elf.len()).max(1),
+                    data: elf.clone(),
+                    owner: loader_id,
+                    executable: true,
+                    rent_epoch: 0,
+                })
+            )]
+        );
+
         // Upgradeable loader
 
         let loader_id = solana_sdk::bpf_loader_upgradeable::id();
@@ -101,14 +299,23 @@ mod tests {
             programdata_address,
         })
         .unwrap();
+        let upgrade_authority = Pubkey::new_unique();
         let mut program_data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
-            slot: 0,
-            upgrade_authority_address: Some(Pubkey::default()),
+            slot: 42,
+            upgrade_authority_address: Some(upgrade_authority),
         })
         .unwrap();
         program_data.extend_from_slice(&elf);
 
-        let accounts = program_accounts(&program_id, &loader_id, &rent, &elf);
+        let accounts = program_accounts(
+            &program_id,
+            &loader_id,
+            ProgramStatus::default(),
+            Some(upgrade_authority),
+            42,
+            &rent,
+            &elf,
+        );
         assert_eq!(accounts.len(), 2);
 
         let (result_program_address, result_program_account) = &accounts[0];
@@ -117,7 +324,7 @@ mod tests {
             result_program_account,
             &AccountSharedData::from(Account {
                 lamports: rent.minimum_balance(data.len()).max(1),
-                data,
+                data: data.clone(),
                 owner: loader_id,
                 executable: true,
                 rent_epoch: 0,
@@ -136,5 +343,226 @@ mod tests {
                 rent_epoch: 0,
             })
         );
+
+        // Upgradeable loader, immutable program
+
+        let accounts = program_accounts(
+            &program_id,
+            &loader_id,
+            ProgramStatus::default(),
+            None,
+            42,
+            &rent,
+            &elf,
+        );
+        assert_eq!(
+            &accounts[1].1.data()[UpgradeableLoaderState::size_of_programdata_metadata()..],
+            &elf
+        );
+
+        // Upgradeable loader, closed program
+
+        let accounts = program_accounts(
+            &program_id,
+            &loader_id,
+            ProgramStatus::Closed,
+            Some(upgrade_authority),
+            42,
+            &rent,
+            &elf,
+        );
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].1.data(), data.as_slice());
+
+        let (result_programdata_address, result_programdata_account) = &accounts[1];
+        assert_eq!(result_programdata_address, &programdata_address);
+        assert_eq!(
+            result_programdata_account,
+            &AccountSharedData::from(Account {
+                lamports: 0,
+                data: bincode::serialize(&UpgradeableLoaderState::Uninitialized).unwrap(),
+                owner: loader_id,
+                executable: false,
+                rent_epoch: 0,
+            })
+        );
+
+        // Loader v4
+
+        let loader_id = solana_sdk::loader_v4::id();
+
+        for (program_status, status) in [
+            (ProgramStatus::Deployed, LoaderV4Status::Deployed),
+            (ProgramStatus::Retracted, LoaderV4Status::Retracted),
+            (ProgramStatus::Finalized, LoaderV4Status::Finalized),
+        ] {
+            let accounts = program_accounts(
+                &program_id,
+                &loader_id,
+                program_status,
+                Some(upgrade_authority),
+                42,
+                &rent,
+                &elf,
+            );
+            assert_eq!(accounts.len(), 1);
+
+            let (result_program_address, result_program_account) = &accounts[0];
+            assert_eq!(result_program_address, &program_id);
+            assert_eq!(result_program_account.owner(), &loader_id);
+            assert!(result_program_account.executable());
+
+            let data = result_program_account.data();
+            let state = solana_loader_v4_program::get_state(data).unwrap();
+            assert_eq!(
+                state,
+                &LoaderV4State {
+                    slot: 42,
+                    authority_address: upgrade_authority,
+                    status,
+                }
+            );
+            assert_eq!(&data[LoaderV4State::program_data_offset()..], &elf);
+        }
+
+        // Loader v4, closed program
+
+        let accounts = program_accounts(
+            &program_id,
+            &loader_id,
+            ProgramStatus::Closed,
+            Some(upgrade_authority),
+            42,
+            &rent,
+            &elf,
+        );
+        assert_eq!(accounts.len(), 1);
+        assert!(accounts[0].1.data().is_empty());
+    }
+
+    #[test]
+    fn test_program_from_accounts_truncated_program_data() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = program_accounts(
+            &program_id,
+            &solana_sdk::bpf_loader_upgradeable::id(),
+            ProgramStatus::Deployed,
+            None,
+            0,
+            &Rent::default(),
+            &[1, 2, 3, 4],
+        );
+
+        // Program data without its padding is shorter than the metadata.
+        accounts[1].1.set_data_from_slice(
+            &bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address: None,
+            })
+            .unwrap(),
+        );
+        assert_eq!(
+            program_from_accounts(&program_id, &accounts),
+            Err(FixtureError::InvalidProgramAccount)
+        );
+    }
+
+    #[test]
+    fn test_program_from_accounts() {
+        let program_id = Pubkey::new_unique();
+        let rent = Rent::default();
+        let elf = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+        // Round trip the program accounts of every loader.
+        for (loader_id, program_status) in [
+            (
+                solana_sdk::bpf_loader_deprecated::id(),
+                ProgramStatus::Deployed,
+            ),
+            (solana_sdk::bpf_loader::id(), ProgramStatus::Deployed),
+            (
+                solana_sdk::bpf_loader_upgradeable::id(),
+                ProgramStatus::Deployed,
+            ),
+            (
+                solana_sdk::bpf_loader_upgradeable::id(),
+                ProgramStatus::Closed,
+            ),
+            (solana_sdk::loader_v4::id(), ProgramStatus::Deployed),
+            (solana_sdk::loader_v4::id(), ProgramStatus::Retracted),
+            (solana_sdk::loader_v4::id(), ProgramStatus::Finalized),
+            (solana_sdk::loader_v4::id(), ProgramStatus::Closed),
+        ] {
+            let accounts = program_accounts(
+                &program_id,
+                &loader_id,
+                program_status,
+                Some(Pubkey::new_unique()),
+                42,
+                &rent,
+                &elf,
+            );
+            assert_eq!(
+                program_from_accounts(&program_id, &accounts),
+                Ok(ProgramFromAccounts {
+                    loader_id,
+                    program_status,
+                    elf: if program_status == ProgramStatus::Closed {
+                        vec![]
+                    } else {
+                        elf.clone()
+                    },
+                })
+            );
+        }
+
+        let accounts = program_accounts(
+            &program_id,
+            &solana_sdk::bpf_loader_upgradeable::id(),
+            ProgramStatus::Deployed,
+            None,
+            0,
+            &rent,
+            &elf,
+        );
+
+        // Missing program account
+        assert_eq!(
+            program_from_accounts(&Pubkey::new_unique(), &accounts),
+            Err(FixtureError::InvalidProgramAccount)
+        );
+
+        // Missing program data account
+        assert_eq!(
+            program_from_accounts(&program_id, &accounts[..1]),
+            Err(FixtureError::InvalidProgramAccount)
+        );
+
+        // Unsupported loader
+        let mut account = accounts[0].1.clone();
+        account.set_owner(Pubkey::new_unique());
+        assert_eq!(
+            program_from_accounts(&program_id, &[(program_id, account)]),
+            Err(FixtureError::InvalidProgramAccount)
+        );
+
+        // Invalid loader v4 status
+        let mut data = loader_v4_state_bytes(&LoaderV4State {
+            slot: 0,
+            authority_address: Pubkey::default(),
+            status: LoaderV4Status::Deployed,
+        });
+        data[LoaderV4State::program_data_offset() - 1] = 1;
+        let account = AccountSharedData::from(Account {
+            lamports: 1,
+            data,
+            owner: solana_sdk::loader_v4::id(),
+            executable: true,
+            rent_epoch: 0,
+        });
+        assert_eq!(
+            program_from_accounts(&program_id, &[(program_id, account)]),
+            Err(FixtureError::InvalidProgramAccount)
+        );
     }
 }
@@ -1,13 +1,22 @@
 //! Solana program runtime loaded programs cache.
 
 use {
+    crate::{
+        fixture::context::ProgramStatus, mock_syscalls, result::ProgramLoadError, syscall_faults,
+        ExecuteOptions,
+    },
     solana_bpf_loader_program::syscalls::create_program_runtime_environment_v1,
+    solana_loader_v4_program::create_program_runtime_environment_v2,
     solana_program_runtime::{
         compute_budget::ComputeBudget,
         invoke_context::BuiltinFunctionWithContext,
-        loaded_programs::{LoadProgramMetrics, LoadedProgram, LoadedProgramsForTxBatch},
+        loaded_programs::{
+            LoadProgramMetrics, LoadedProgram, LoadedProgramType, LoadedProgramsForTxBatch,
+            ProgramRuntimeEnvironments, DELAY_VISIBILITY_SLOT_OFFSET,
+        },
+        solana_rbpf::{elf::ElfError, error::EbpfError, verifier::VerifierError},
     },
-    solana_sdk::{feature_set::FeatureSet, pubkey::Pubkey},
+    solana_sdk::{feature_set::FeatureSet, loader_v4::LoaderV4State, pubkey::Pubkey},
     std::sync::Arc,
 };
 
@@ -16,42 +25,131 @@ struct Builtin {
     program_id: Pubkey,
     name: &'static str,
     entrypoint: BuiltinFunctionWithContext,
+    // The feature which enables the builtin, if any.
+    feature_id: Option<Pubkey>,
 }
 
 static BUILTINS: &[Builtin] = &[
+    Builtin {
+        program_id: solana_sdk::bpf_loader_deprecated::id(),
+        name: "solana_bpf_loader_deprecated_program",
+        entrypoint: solana_bpf_loader_program::Entrypoint::vm,
+        feature_id: None,
+    },
     Builtin {
         program_id: solana_sdk::bpf_loader::id(),
         name: "solana_bpf_loader_program",
         entrypoint: solana_bpf_loader_program::Entrypoint::vm,
+        feature_id: None,
     },
     Builtin {
         program_id: solana_sdk::bpf_loader_upgradeable::id(),
         name: "solana_bpf_loader_upgradeable_program",
         entrypoint: solana_bpf_loader_program::Entrypoint::vm,
+        feature_id: None,
+    },
+    Builtin {
+        program_id: solana_sdk::loader_v4::id(),
+        name: "loader_v4",
+        entrypoint: solana_loader_v4_program::Entrypoint::vm,
+        feature_id: Some(solana_sdk::feature_set::enable_program_runtime_v2_and_loader_v4::id()),
+    },
+    Builtin {
+        program_id: solana_sdk::system_program::id(),
+        name: "system_program",
+        entrypoint: solana_system_program::system_processor::Entrypoint::vm,
+        feature_id: None,
     },
     /* Additional builtins... */
 ];
 
-/// Build the loaded programs cache with a provided program and the above
-/// builtins.
-pub fn build_loaded_programs_cache(
-    program_id: &Pubkey,
-    loader_id: &Pubkey,
+fn enabled_builtins(feature_set: &FeatureSet) -> impl Iterator<Item = &'static Builtin> + '_ {
+    BUILTINS.iter().filter(|builtin| {
+        builtin
+            .feature_id
+            .map_or(true, |feature_id| feature_set.is_active(&feature_id))
+    })
+}
+
+/// The name of the builtin program with the given program ID, if it is
+/// enabled by the feature set.
+pub fn builtin_name(program_id: &Pubkey, feature_set: &FeatureSet) -> Option<&'static str> {
+    enabled_builtins(feature_set)
+        .find(|builtin| builtin.program_id == *program_id)
+        .map(|builtin| builtin.name)
+}
+
+fn to_program_load_error(error: Box<dyn std::error::Error>) -> ProgramLoadError {
+    let error = match error.downcast::<ElfError>() {
+        Ok(error) => return ProgramLoadError::InvalidElf(error.to_string()),
+        Err(error) => error,
+    };
+    let error = match error.downcast::<VerifierError>() {
+        Ok(error) => return ProgramLoadError::VerificationFailed(error.to_string()),
+        Err(error) => error,
+    };
+    match error.downcast::<EbpfError>() {
+        Ok(error) => ProgramLoadError::JitCompilationFailed(error.to_string()),
+        Err(error) => ProgramLoadError::InvalidElf(error.to_string()),
+    }
+}
+
+// Build the loaded programs cache for a given slot, with only the above
+// builtins, returning any error creating the program runtime environments.
+// The environments include the mock syscalls, and the syscalls of both are
+// intercepted if faults are to be injected.
+fn build_builtins_cache(
+    slot: u64,
     compute_budget: &ComputeBudget,
     feature_set: &FeatureSet,
-    metrics: &mut LoadProgramMetrics,
-    elf: &[u8],
-) -> LoadedProgramsForTxBatch {
-    let program_runtime_environment =
-        create_program_runtime_environment_v1(feature_set, compute_budget, false, false).unwrap();
+    options: &ExecuteOptions,
+) -> (LoadedProgramsForTxBatch, Option<ProgramLoadError>) {
+    let mut program_load_error = None;
+    let with_mock_syscalls = |environment| {
+        if options.mock_syscalls.is_empty() {
+            return Ok(environment);
+        }
+        mock_syscalls::register_mock_syscalls(&environment, options.mock_syscalls)
+    };
+
+    let mut environments = ProgramRuntimeEnvironments::default();
+    match with_mock_syscalls(create_program_runtime_environment_v2(
+        compute_budget,
+        options.debugging_features,
+    )) {
+        Ok(environment) => environments.program_runtime_v2 = Arc::new(environment),
+        Err(error) => {
+            program_load_error = Some(ProgramLoadError::InvalidEnvironment(error.to_string()))
+        }
+    }
+    match create_program_runtime_environment_v1(
+        feature_set,
+        compute_budget,
+        false,
+        options.debugging_features,
+    )
+    .and_then(|environment| Ok(with_mock_syscalls(environment)?))
+    {
+        Ok(environment) => environments.program_runtime_v1 = Arc::new(environment),
+        Err(error) => {
+            program_load_error = Some(ProgramLoadError::InvalidEnvironment(error.to_string()))
+        }
+    }
 
-    let mut cache = LoadedProgramsForTxBatch::default();
+    if !options.syscall_faults.is_empty() {
+        if let Err(error) = syscall_faults::intercept_syscalls(&mut environments) {
+            program_load_error = Some(error);
+        }
+    }
 
-    BUILTINS.iter().for_each(
+    let mut cache = LoadedProgramsForTxBatch::new(slot, environments);
+
+    enabled_builtins(feature_set).for_each(
         |Builtin {
              program_id,
              name,
              entrypoint,
+             ..
          }| {
             cache.replenish(
                 *program_id,
@@ -60,22 +158,217 @@ pub fn build_loaded_programs_cache(
         },
     );
 
+    (cache, program_load_error)
+}
+
+/// Build the loaded programs cache for a given slot, with a provided program
+/// and the above builtins.
+///
+/// A program deployed in the cache's slot is not visible until the next one.
+/// A program which fails to load is tombstoned, and the error is returned
+/// alongside the cache. Builtin programs need no loading.
+///
+/// With debugging features, the VM records an instruction trace of each
+/// program execution, at the cost of speed.
+#[allow(clippy::too_many_arguments)]
+pub fn build_loaded_programs_cache(
+    program_id: &Pubkey,
+    loader_id: &Pubkey,
+    program_status: ProgramStatus,
+    deployment_slot: Option<u64>,
+    slot: u64,
+    compute_budget: &ComputeBudget,
+    feature_set: &FeatureSet,
+    metrics: &mut LoadProgramMetrics,
+    elf: &[u8],
+    options: &ExecuteOptions,
+) -> (LoadedProgramsForTxBatch, Option<ProgramLoadError>) {
+    let (mut cache, mut program_load_error) =
+        build_builtins_cache(slot, compute_budget, feature_set, options);
+    let environments = cache.environments.clone();
+
+    if builtin_name(program_id, feature_set).is_some() {
+        return (cache, program_load_error);
+    }
+
+    // Programs deployed before the current slot are effective immediately.
+    let (deployment_slot, effective_slot) = match deployment_slot {
+        Some(deployment_slot) => (
+            deployment_slot,
+            deployment_slot.saturating_add(DELAY_VISIBILITY_SLOT_OFFSET),
+        ),
+        None => (0, 0),
+    };
+
+    let is_loader_v4 = *loader_id == solana_sdk::loader_v4::id();
+    let (environment, account_size) = if is_loader_v4 {
+        (
+            environments.program_runtime_v2.clone(),
+            LoaderV4State::program_data_offset().saturating_add(elf.len()),
+        )
+    } else {
+        (environments.program_runtime_v1.clone(), elf.len())
+    };
+
+    let is_supported_loader = is_loader_v4
+        || *loader_id == solana_sdk::bpf_loader_deprecated::id()
+        || *loader_id == solana_sdk::bpf_loader::id()
+        || *loader_id == solana_sdk::bpf_loader_upgradeable::id();
+
+    let loaded_program = match program_status {
+        // Programs owned by an unsupported loader can't be invoked at all.
+        _ if !is_supported_loader => {
+            return (cache, Some(ProgramLoadError::UnsupportedLoader));
+        }
+        ProgramStatus::Closed => {
+            LoadedProgram::new_tombstone(deployment_slot, LoadedProgramType::Closed)
+        }
+        // Only loader v4 retracts programs, which are then not loaded.
+        ProgramStatus::Retracted if is_loader_v4 => {
+            LoadedProgram::new_tombstone(deployment_slot, LoadedProgramType::Closed)
+        }
+        _ if program_load_error.is_some() => LoadedProgram::new_tombstone(
+            deployment_slot,
+            LoadedProgramType::FailedVerification(environment),
+        ),
+        _ => LoadedProgram::new(
+            loader_id,
+            environment.clone(),
+            deployment_slot,
+            effective_slot,
+            None,
+            elf,
+            account_size,
+            metrics,
+        )
+        .unwrap_or_else(|error| {
+            program_load_error = Some(to_program_load_error(error));
+            LoadedProgram::new_tombstone(
+                deployment_slot,
+                LoadedProgramType::FailedVerification(environment),
+            )
+        }),
+    };
+
+    cache.replenish(*program_id, Arc::new(loaded_program));
+
+    (cache, program_load_error)
+}
+
+/// Build the loaded programs cache for a given slot, with a native program
+/// registered as a builtin alongside the above builtins.
+#[cfg(feature = "native")]
+pub fn build_native_programs_cache(
+    program_id: &Pubkey,
+    entrypoint: BuiltinFunctionWithContext,
+    slot: u64,
+    compute_budget: &ComputeBudget,
+    feature_set: &FeatureSet,
+) -> (LoadedProgramsForTxBatch, Option<ProgramLoadError>) {
+    let (mut cache, program_load_error) = build_builtins_cache(
+        slot,
+        compute_budget,
+        feature_set,
+        &ExecuteOptions::default(),
+    );
     cache.replenish(
         *program_id,
-        Arc::new(
-            LoadedProgram::new(
-                loader_id,
-                Arc::new(program_runtime_environment),
-                0,
-                0,
-                None,
-                elf,
-                elf.len(),
-                metrics,
-            )
-            .unwrap(),
-        ),
+        Arc::new(LoadedProgram::new_builtin(0, 0, entrypoint)),
     );
+    (cache, program_load_error)
+}
+
+/// Replace the entrypoint of the SBF loaders which execute programs in the
+/// VM, such as to execute the program under test under a debugger.
+#[cfg(feature = "debugger")]
+pub(crate) fn replace_loader_entrypoints(
+    cache: &mut LoadedProgramsForTxBatch,
+    entrypoint: BuiltinFunctionWithContext,
+    feature_set: &FeatureSet,
+) {
+    let loader_ids = [
+        solana_sdk::bpf_loader_deprecated::id(),
+        solana_sdk::bpf_loader::id(),
+        solana_sdk::bpf_loader_upgradeable::id(),
+    ];
+    enabled_builtins(feature_set)
+        .filter(|builtin| loader_ids.contains(&builtin.program_id))
+        .for_each(
+            |Builtin {
+                 program_id, name, ..
+             }| {
+                cache.replenish(
+                    *program_id,
+                    Arc::new(LoadedProgram::new_builtin(0, name.len(), entrypoint)),
+                );
+            },
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_cache(
+        program_id: &Pubkey,
+        loader_id: &Pubkey,
+        elf: &[u8],
+    ) -> (LoadedProgramsForTxBatch, Option<ProgramLoadError>) {
+        build_loaded_programs_cache(
+            program_id,
+            loader_id,
+            ProgramStatus::Deployed,
+            None,
+            0,
+            &ComputeBudget::default(),
+            &FeatureSet::all_enabled(),
+            &mut LoadProgramMetrics::default(),
+            elf,
+            &ExecuteOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_build_loaded_programs_cache_invalid_elf() {
+        let program_id = Pubkey::new_unique();
+
+        for loader_id in [
+            solana_sdk::bpf_loader_deprecated::id(),
+            solana_sdk::bpf_loader::id(),
+            solana_sdk::bpf_loader_upgradeable::id(),
+            solana_sdk::loader_v4::id(),
+        ] {
+            let (cache, program_load_error) = build_cache(&program_id, &loader_id, &[1, 2, 3, 4]);
+            assert!(matches!(
+                program_load_error,
+                Some(ProgramLoadError::InvalidElf(_))
+            ));
+            assert!(matches!(
+                cache.find(&program_id).unwrap().program,
+                LoadedProgramType::FailedVerification(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_build_loaded_programs_cache_unsupported_loader() {
+        let program_id = Pubkey::new_unique();
+        let (cache, program_load_error) = build_cache(&program_id, &Pubkey::new_unique(), &[]);
+        assert_eq!(
+            program_load_error,
+            Some(ProgramLoadError::UnsupportedLoader)
+        );
+        assert!(cache.find(&program_id).is_none());
+    }
 
-    cache
+    #[test]
+    fn test_build_loaded_programs_cache_builtin() {
+        let program_id = solana_sdk::bpf_loader_upgradeable::id();
+        let (cache, program_load_error) = build_cache(&program_id, &Pubkey::new_unique(), &[]);
+        assert_eq!(program_load_error, None);
+        assert!(matches!(
+            cache.find(&program_id).unwrap().program,
+            LoadedProgramType::Builtin(_)
+        ));
+    }
 }
@@ -0,0 +1,250 @@
+//! Results of executing an instruction in the simulated program runtime.
+
+use {
+    crate::backtrace::Backtrace,
+    solana_program_runtime::loaded_programs::{LoadedProgram, LoadedProgramType},
+    solana_sdk::{
+        account::AccountSharedData,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        transaction_context::{InstructionContext, TransactionContext, TransactionReturnData},
+    },
+    thiserror::Error,
+};
+
+/// Errors possible for loading the program under test.
+///
+/// A program which fails to load is tombstoned, as in the runtime, so
+/// invoking it fails with `InstructionError::InvalidAccountData`.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum ProgramLoadError {
+    /// The program's loader is not supported by the harness.
+    #[error("Unsupported loader")]
+    UnsupportedLoader,
+    /// The program runtime environment could not be created.
+    #[error("Invalid program runtime environment: {0}")]
+    InvalidEnvironment(String),
+    /// The program ELF could not be loaded.
+    #[error("Invalid ELF: {0}")]
+    InvalidElf(String),
+    /// The program was rejected by the verifier.
+    #[error("Verification failed: {0}")]
+    VerificationFailed(String),
+    /// The program could not be JIT compiled.
+    #[error("JIT compilation failed: {0}")]
+    JitCompilationFailed(String),
+}
+
+/// The status of a program modified by an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModifiedProgramStatus {
+    /// The program was deployed or upgraded.
+    Deployed,
+    /// The program was closed.
+    Closed,
+    /// The program failed verification.
+    FailedVerification,
+}
+
+/// A program deployed, upgraded or closed by an instruction, as recorded in
+/// the runtime's programs cache.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModifiedProgram {
+    /// The program ID.
+    pub program_id: Pubkey,
+    /// The slot in which the program was modified.
+    pub deployment_slot: u64,
+    /// The status of the program.
+    pub status: ModifiedProgramStatus,
+}
+
+impl ModifiedProgram {
+    pub(crate) fn from_cache_entry(program_id: Pubkey, entry: &LoadedProgram) -> Option<Self> {
+        let status = match entry.program {
+            // Deployed programs are not visible until the next slot.
+            LoadedProgramType::LegacyV0(_)
+            | LoadedProgramType::LegacyV1(_)
+            | LoadedProgramType::Typed(_)
+            | LoadedProgramType::DelayVisibility => ModifiedProgramStatus::Deployed,
+            LoadedProgramType::Closed => ModifiedProgramStatus::Closed,
+            LoadedProgramType::FailedVerification(_) => ModifiedProgramStatus::FailedVerification,
+            _ => return None,
+        };
+        Some(Self {
+            program_id,
+            deployment_slot: entry.deployment_slot,
+            status,
+        })
+    }
+}
+
+/// An instruction issued by a cross-program invocation, as recorded in the
+/// transaction context's instruction trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InnerInstruction {
+    /// The instruction, with its accounts' privileges within the callee.
+    pub instruction: Instruction,
+    /// The invocation stack height of the instruction, starting at 2 for
+    /// instructions invoked by the program under test.
+    pub stack_height: u32,
+}
+
+impl InnerInstruction {
+    fn from_instruction_context(
+        transaction_context: &TransactionContext,
+        instruction_context: &InstructionContext,
+    ) -> Result<Self, InstructionError> {
+        let accounts = (0..instruction_context.get_number_of_instruction_accounts())
+            .map(|index_in_instruction| {
+                let index_in_transaction = instruction_context
+                    .get_index_of_instruction_account_in_transaction(index_in_instruction)?;
+                Ok(AccountMeta {
+                    pubkey: *transaction_context
+                        .get_key_of_account_at_index(index_in_transaction)?,
+                    is_signer: instruction_context
+                        .is_instruction_account_signer(index_in_instruction)?,
+                    is_writable: instruction_context
+                        .is_instruction_account_writable(index_in_instruction)?,
+                })
+            })
+            .collect::<Result<Vec<_>, InstructionError>>()?;
+        Ok(Self {
+            instruction: Instruction {
+                program_id: *instruction_context.get_last_program_key(transaction_context)?,
+                accounts,
+                data: instruction_context.get_instruction_data().to_vec(),
+            },
+            stack_height: instruction_context.get_stack_height() as u32,
+        })
+    }
+
+    /// The inner instructions in a transaction context's instruction trace,
+    /// in the order they were invoked. The top-level instruction is omitted.
+    pub(crate) fn from_instruction_trace(transaction_context: &TransactionContext) -> Vec<Self> {
+        (1..transaction_context.get_instruction_trace_length())
+            .filter_map(|index_in_trace| {
+                let instruction_context = transaction_context
+                    .get_instruction_context_at_index_in_trace(index_in_trace)
+                    .ok()?;
+                Self::from_instruction_context(transaction_context, instruction_context).ok()
+            })
+            .collect()
+    }
+}
+
+/// The result of executing a fixture's instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionResult {
+    /// The result of the instruction.
+    pub result: Result<(), InstructionError>,
+    /// The number of compute units consumed by the instruction.
+    pub compute_units_consumed: u64,
+    /// The resulting state of each input account, in input order.
+    pub resulting_accounts: Vec<(Pubkey, AccountSharedData)>,
+    /// The error loading the program, if it failed to load.
+    pub program_load_error: Option<ProgramLoadError>,
+    /// The programs deployed, upgraded or closed by the instruction.
+    pub modified_programs: Vec<ModifiedProgram>,
+    /// The data returned by the instruction and the program which set it, or
+    /// `None` if no data was returned.
+    pub return_data: Option<TransactionReturnData>,
+    /// The instructions issued by cross-program invocations, in the order
+    /// they were invoked.
+    pub inner_instructions: Vec<InnerInstruction>,
+    /// The backtrace of the SBF program under test if it failed, when
+    /// captured by a harness with backtraces enabled.
+    pub backtrace: Option<Backtrace>,
+}
+
+impl ExecutionResult {
+    /// Whether two results have the same outcome: the same instruction
+    /// result, the same resulting account states, the same modified programs,
+    /// the same return data and the same inner instructions. Compute unit
+    /// consumption and backtraces are not considered.
+    pub fn same_outcome(&self, other: &Self) -> bool {
+        self.result == other.result
+            && self.resulting_accounts == other.resulting_accounts
+            && self.modified_programs == other.modified_programs
+            && self.return_data == other.return_data
+            && self.inner_instructions == other.inner_instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::account::{Account, WritableAccount},
+    };
+
+    #[test]
+    fn test_same_outcome() {
+        let pubkey = Pubkey::new_unique();
+        let account = AccountSharedData::from(Account {
+            lamports: 42,
+            data: vec![1, 2, 3],
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        });
+
+        let result = ExecutionResult {
+            result: Ok(()),
+            compute_units_consumed: 100,
+            resulting_accounts: vec![(pubkey, account.clone())],
+            program_load_error: None,
+            modified_programs: vec![],
+            return_data: None,
+            inner_instructions: vec![],
+            backtrace: None,
+        };
+
+        // Compute units are not considered.
+        assert!(result.same_outcome(&ExecutionResult {
+            compute_units_consumed: 200,
+            ..result.clone()
+        }));
+
+        // Different instruction result.
+        assert!(!result.same_outcome(&ExecutionResult {
+            result: Err(InstructionError::InvalidAccountData),
+            ..result.clone()
+        }));
+
+        // Different account state.
+        let mut modified_account = account;
+        modified_account.set_lamports(0);
+        assert!(!result.same_outcome(&ExecutionResult {
+            resulting_accounts: vec![(pubkey, modified_account)],
+            ..result.clone()
+        }));
+
+        // Different modified programs.
+        assert!(!result.same_outcome(&ExecutionResult {
+            modified_programs: vec![ModifiedProgram {
+                program_id: Pubkey::new_unique(),
+                deployment_slot: 0,
+                status: ModifiedProgramStatus::Closed,
+            }],
+            ..result.clone()
+        }));
+
+        // Different return data.
+        assert!(!result.same_outcome(&ExecutionResult {
+            return_data: Some(TransactionReturnData {
+                program_id: Pubkey::new_unique(),
+                data: vec![1],
+            }),
+            ..result.clone()
+        }));
+
+        // Different inner instructions.
+        assert!(!result.same_outcome(&ExecutionResult {
+            inner_instructions: vec![InnerInstruction {
+                instruction: Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+                stack_height: 2,
+            }],
+            ..result.clone()
+        }));
+    }
+}
@@ -0,0 +1,414 @@
+//! Syscall fault injection.
+//!
+//! Programs' error paths for failures which real inputs can't easily
+//! trigger, such as a failed cross-program invocation or a sysvar syscall
+//! failing, can be tested by injecting faults into chosen syscall
+//! invocations.
+//!
+//! Every syscall of the SBF program runtime environments, for programs of
+//! every loader, is replaced by an interceptor, which counts the invocations
+//! and either calls the original syscall or applies the fault. Builtin functions are plain function
+//! pointers, so each syscall gets an interceptor of its own, which finds the
+//! original through its slot.
+
+use {
+    crate::result::ProgramLoadError,
+    solana_program_runtime::{
+        invoke_context::InvokeContext, loaded_programs::ProgramRuntimeEnvironments,
+    },
+    solana_rbpf::{
+        declare_builtin_function,
+        memory_region::MemoryMapping,
+        program::{BuiltinFunction, BuiltinProgram, FunctionRegistry},
+        vm::EbpfVm,
+    },
+    solana_sdk::instruction::InstructionError,
+    std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        sync::Arc,
+    },
+};
+
+/// A fault injected into invocations of SBF program syscalls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyscallFault {
+    /// The name of the syscall, such as `sol_get_clock_sysvar` or
+    /// `sol_invoke_signed_rust`, or `None` for any syscall.
+    pub syscall: Option<String>,
+    /// The invocation to fault, counting from zero across the instruction's
+    /// invocations of the syscall, including by cross-program invocations
+    /// of the program, or `None` for every invocation.
+    pub invocation: Option<u64>,
+    /// The effect of the fault.
+    pub effect: SyscallFaultEffect,
+}
+
+/// The effect of a fault injected into a syscall invocation, in place of
+/// the syscall.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyscallFaultEffect {
+    /// Abort the program with an instruction error, as a failed syscall or
+    /// cross-program invocation does.
+    Error(InstructionError),
+    /// Return a value to the program, such as `1` for
+    /// `sol_try_find_program_address` finding no address.
+    Return(u64),
+    /// Exhaust the compute meter, aborting the program with
+    /// `InstructionError::ComputationalBudgetExceeded`.
+    ExhaustComputeMeter,
+}
+
+// The faults injected into executions on this thread, and the invocations
+// counted so far.
+#[derive(Default)]
+struct FaultState {
+    faults: Vec<SyscallFault>,
+    invocations: HashMap<String, u64>,
+    total_invocations: u64,
+}
+
+impl FaultState {
+    // Count an invocation of a syscall, returning the effect of the first
+    // fault it matches, if any.
+    fn invoke(&mut self, syscall: &str) -> Option<SyscallFaultEffect> {
+        let invocation = self.invocations.entry(syscall.to_string()).or_default();
+        let (syscall_invocation, total_invocation) = (*invocation, self.total_invocations);
+        *invocation += 1;
+        self.total_invocations += 1;
+
+        self.faults
+            .iter()
+            .find(|fault| match &fault.syscall {
+                Some(name) => {
+                    name == syscall
+                        && fault
+                            .invocation
+                            .map_or(true, |invocation| invocation == syscall_invocation)
+                }
+                None => fault
+                    .invocation
+                    .map_or(true, |invocation| invocation == total_invocation),
+            })
+            .map(|fault| fault.effect.clone())
+    }
+}
+
+type Syscall = BuiltinFunction<InvokeContext<'static>>;
+
+thread_local! {
+    static FAULT_STATE: RefCell<FaultState> = RefCell::default();
+    // The name and original function of each intercepted syscall, by slot.
+    static SLOTS: RefCell<Vec<(String, Syscall)>> = const { RefCell::new(Vec::new()) };
+    // The effect of the fault being applied.
+    static EFFECT: Cell<Option<SyscallFaultEffect>> = const { Cell::new(None) };
+}
+
+/// Run `f` with `faults` injected into the syscalls of environments built
+/// by `intercept_syscalls` on this thread, both the upgradeable loader's and
+/// loader v4's.
+pub(crate) fn with_syscall_faults<T>(faults: &[SyscallFault], f: impl FnOnce() -> T) -> T {
+    let _guard = FaultStateGuard(FAULT_STATE.with(|state| {
+        state.replace(FaultState {
+            faults: faults.to_vec(),
+            ..FaultState::default()
+        })
+    }));
+    f()
+}
+
+// Restores the previous fault state when dropped, including when the
+// instruction panics.
+struct FaultStateGuard(FaultState);
+
+impl Drop for FaultStateGuard {
+    fn drop(&mut self) {
+        FAULT_STATE.with(|state| state.replace(std::mem::take(&mut self.0)));
+    }
+}
+
+/// Replace every syscall of the SBF program runtime environments with an
+/// interceptor, for faults to be injected on this thread. Each syscall takes
+/// one of a fixed number of interceptors, so environments with more syscalls
+/// than interceptors are rejected.
+pub(crate) fn intercept_syscalls(
+    environments: &mut ProgramRuntimeEnvironments,
+) -> Result<(), ProgramLoadError> {
+    let mut slots = Vec::new();
+    let mut intercept = |environment: &BuiltinProgram<InvokeContext<'static>>| {
+        let mut functions = FunctionRegistry::default();
+        for (_, (name, function)) in environment.get_function_registry().iter() {
+            let interceptor = INTERCEPTORS.get(slots.len()).ok_or_else(|| {
+                ProgramLoadError::InvalidEnvironment(format!(
+                    "More than {} syscalls to intercept",
+                    INTERCEPTORS.len()
+                ))
+            })?;
+            functions
+                .register_function_hashed(name, *interceptor)
+                .map_err(|error| ProgramLoadError::InvalidEnvironment(error.to_string()))?;
+            slots.push((String::from_utf8_lossy(name).into_owned(), function));
+        }
+        Ok(Arc::new(BuiltinProgram::new_loader(
+            *environment.get_config(),
+            functions,
+        )))
+    };
+    let program_runtime_v1 = intercept(&environments.program_runtime_v1)?;
+    let program_runtime_v2 = intercept(&environments.program_runtime_v2)?;
+    environments.program_runtime_v1 = program_runtime_v1;
+    environments.program_runtime_v2 = program_runtime_v2;
+    SLOTS.with(|slots_by_thread| slots_by_thread.replace(slots));
+    Ok(())
+}
+
+fn intercept<const SLOT: usize>(
+    vm: *mut EbpfVm<InvokeContext<'static>>,
+    arg_a: u64,
+    arg_b: u64,
+    arg_c: u64,
+    arg_d: u64,
+    arg_e: u64,
+) {
+    let (name, original) = SLOTS.with(|slots| slots.borrow()[SLOT].clone());
+    match FAULT_STATE.with(|state| state.borrow_mut().invoke(&name)) {
+        Some(effect) => {
+            EFFECT.with(|current| current.set(Some(effect)));
+            injected_fault::InjectedFault::vm(vm, arg_a, arg_b, arg_c, arg_d, arg_e);
+        }
+        None => original(vm, arg_a, arg_b, arg_c, arg_d, arg_e),
+    }
+}
+
+// The builtin function applying the fault being injected, kept out of the
+// public API.
+mod injected_fault {
+    use super::*;
+
+    declare_builtin_function!(
+        InjectedFault,
+        fn rust(
+            invoke_context: &mut InvokeContext,
+            _arg_a: u64,
+            _arg_b: u64,
+            _arg_c: u64,
+            _arg_d: u64,
+            _arg_e: u64,
+            _memory_mapping: &mut MemoryMapping,
+        ) -> Result<u64, Box<dyn std::error::Error>> {
+            match EFFECT.with(|effect| effect.take()) {
+                Some(SyscallFaultEffect::Error(error)) => Err(Box::new(error)),
+                Some(SyscallFaultEffect::Return(value)) => Ok(value),
+                Some(SyscallFaultEffect::ExhaustComputeMeter) => {
+                    invoke_context.mock_set_remaining(0);
+                    Err(Box::new(InstructionError::ComputationalBudgetExceeded))
+                }
+                None => Err(Box::new(InstructionError::GenericError)),
+            }
+        }
+    );
+}
+
+macro_rules! interceptors {
+    ($($slot:literal)*) => {
+        [$(intercept::<$slot> as Syscall),*]
+    };
+}
+
+// Enough interceptors for every syscall.
+static INTERCEPTORS: [Syscall; 64] = interceptors!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+    32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61
+    62 63
+);
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::mock_syscalls::{register_mock_syscalls, MockSyscall},
+        solana_bpf_loader_program::syscalls::{
+            create_program_runtime_environment_v1, SyscallAbort,
+        },
+        solana_loader_v4_program::create_program_runtime_environment_v2,
+        solana_program_runtime::{compute_budget::ComputeBudget, with_mock_invoke_context},
+        solana_rbpf::{
+            assembler::assemble,
+            error::{EbpfError, ProgramResult},
+        },
+        solana_sdk::feature_set::FeatureSet,
+    };
+
+    fn fault(syscall: Option<&str>, invocation: Option<u64>, value: u64) -> SyscallFault {
+        SyscallFault {
+            syscall: syscall.map(str::to_string),
+            invocation,
+            effect: SyscallFaultEffect::Return(value),
+        }
+    }
+
+    #[test]
+    fn test_invoke() {
+        let mut state = FaultState {
+            faults: vec![
+                fault(Some("sol_get_clock_sysvar"), Some(1), 1),
+                fault(Some("sol_log_"), None, 2),
+                fault(None, Some(4), 3),
+            ],
+            ..FaultState::default()
+        };
+        let effects = [
+            "sol_get_clock_sysvar",
+            "sol_get_clock_sysvar",
+            "sol_log_",
+            "sol_get_clock_sysvar",
+            "sol_memcpy_",
+            "sol_log_",
+        ]
+        .map(|syscall| state.invoke(syscall));
+        assert_eq!(
+            effects,
+            [
+                None,
+                Some(SyscallFaultEffect::Return(1)),
+                Some(SyscallFaultEffect::Return(2)),
+                None,
+                Some(SyscallFaultEffect::Return(3)),
+                Some(SyscallFaultEffect::Return(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_syscall_faults_panic() {
+        let faults = [fault(None, None, 1)];
+        let result = std::panic::catch_unwind(|| {
+            with_syscall_faults(&faults, || {
+                assert_eq!(FAULT_STATE.with(|state| state.borrow().faults.len()), 1);
+                panic!("Instruction panicked");
+            })
+        });
+        assert!(result.is_err());
+        assert!(FAULT_STATE.with(|state| state.borrow().faults.is_empty()));
+    }
+
+    #[test]
+    fn test_intercept_syscalls() {
+        let environment = Arc::new(
+            create_program_runtime_environment_v1(
+                &FeatureSet::all_enabled(),
+                &ComputeBudget::default(),
+                false,
+                false,
+            )
+            .unwrap(),
+        );
+        // Loader v4 programs only have the mock syscalls.
+        let environment_v2 = register_mock_syscalls(
+            &create_program_runtime_environment_v2(&ComputeBudget::default(), false),
+            &[MockSyscall::new("sol_mock", SyscallAbort::vm)],
+        )
+        .unwrap();
+        let mut environments = ProgramRuntimeEnvironments {
+            program_runtime_v1: environment.clone(),
+            program_runtime_v2: Arc::new(environment_v2),
+        };
+        intercept_syscalls(&mut environments).unwrap();
+        let intercepted = environments.program_runtime_v1.clone();
+        let syscalls = environment.get_function_registry().iter().count();
+        assert_eq!(
+            environments
+                .program_runtime_v2
+                .get_function_registry()
+                .lookup_by_name(b"sol_mock")
+                .map(|(_, function)| function as usize),
+            Some(INTERCEPTORS[syscalls] as usize)
+        );
+
+        // Syscalls beyond the interceptors are rejected rather than left
+        // unfaulted.
+        assert!(2 * syscalls > INTERCEPTORS.len());
+        let mut environments = ProgramRuntimeEnvironments {
+            program_runtime_v1: environment.clone(),
+            program_runtime_v2: environment.clone(),
+        };
+        assert!(matches!(
+            intercept_syscalls(&mut environments),
+            Err(ProgramLoadError::InvalidEnvironment(_))
+        ));
+
+        let names = |environment: &BuiltinProgram<InvokeContext<'static>>| {
+            let mut names = environment
+                .get_function_registry()
+                .iter()
+                .map(|(key, (name, _))| (key, name.to_vec()))
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&intercepted), names(&environment));
+        assert_eq!(intercepted.get_config(), environment.get_config());
+
+        // The program returns the remaining compute units on its second
+        // syscall.
+        let executable = assemble::<InvokeContext<'static>>(
+            "syscall sol_remaining_compute_units
+             syscall sol_remaining_compute_units
+             exit",
+            intercepted.clone(),
+        )
+        .unwrap();
+        let execute = |faults: &[SyscallFault]| {
+            with_syscall_faults(faults, || {
+                with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+                // SAFETY: The invoke context outlives the VM, as in the
+                // loader.
+                let invoke_context = unsafe {
+                    std::mem::transmute::<&mut InvokeContext, &mut InvokeContext<'static>>(
+                        &mut invoke_context,
+                    )
+                };
+                let sbpf_version = executable.get_sbpf_version();
+                let memory_mapping =
+                    MemoryMapping::new(vec![], executable.get_config(), sbpf_version).unwrap();
+                let mut vm = EbpfVm::new(
+                    intercepted.clone(),
+                    sbpf_version,
+                    invoke_context,
+                    memory_mapping,
+                    0,
+                );
+                vm.execute_program(&executable, true).1
+            })
+        };
+
+        assert!(matches!(execute(&[]), ProgramResult::Ok(remaining) if remaining > 5));
+        assert!(matches!(
+            execute(&[fault(None, Some(0), 5)]),
+            ProgramResult::Ok(remaining) if remaining > 5
+        ));
+        assert!(matches!(
+            execute(&[fault(Some("sol_remaining_compute_units"), Some(1), 5)]),
+            ProgramResult::Ok(5)
+        ));
+
+        let error = |effect| {
+            let ProgramResult::Err(EbpfError::SyscallError(error)) = execute(&[SyscallFault {
+                syscall: Some(String::from("sol_remaining_compute_units")),
+                invocation: None,
+                effect,
+            }]) else {
+                panic!("Expected a syscall error");
+            };
+            *error.downcast::<InstructionError>().unwrap()
+        };
+        assert_eq!(
+            error(SyscallFaultEffect::Error(InstructionError::Custom(7))),
+            InstructionError::Custom(7)
+        );
+        assert_eq!(
+            error(SyscallFaultEffect::ExhaustComputeMeter),
+            InstructionError::ComputationalBudgetExceeded
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! The layout of the VM's instruction trace, and the call frames recovered
+//! from it.
+//!
+//! Each trace entry holds the eleven registers followed by the program
+//! counter. The frame pointer moves on every call and return, so frames are
+//! found by comparing it against the frame pointers of the enclosing frames.
+
+// Each SBF instruction slot is 8 bytes.
+pub(crate) const INSN_SIZE: u64 = 8;
+
+// The frame pointer and program counter indices in a VM instruction trace
+// entry.
+pub(crate) const FRAME_POINTER_INDEX: usize = 10;
+pub(crate) const PC_INDEX: usize = 11;
+
+// Walk the call frames of an execution's trace, outermost first. `enter`
+// creates a frame at the program counter which entered it, and `step` is
+// called with the frames at every trace entry, returning the frames at the
+// end of the trace.
+pub(crate) fn walk_frames<F>(
+    trace: &[[u64; 12]],
+    mut enter: impl FnMut(u64) -> F,
+    mut step: impl FnMut(&mut [F], u64),
+) -> Vec<F> {
+    let mut frame_pointers: Vec<u64> = Vec::new();
+    let mut frames = Vec::new();
+    for state in trace {
+        let frame_pointer = state[FRAME_POINTER_INDEX];
+        let pc = state[PC_INDEX];
+        while frames.len() > 1 && frame_pointer < *frame_pointers.last().unwrap() {
+            frame_pointers.pop();
+            frames.pop();
+        }
+        match frame_pointers.last() {
+            Some(top_frame_pointer) if frame_pointer <= *top_frame_pointer => {}
+            _ => {
+                frame_pointers.push(frame_pointer);
+                frames.push(enter(pc));
+            }
+        }
+        step(&mut frames, pc);
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(frame_pointer: u64, pc: u64) -> [u64; 12] {
+        let mut state = [0; 12];
+        state[FRAME_POINTER_INDEX] = frame_pointer;
+        state[PC_INDEX] = pc;
+        state
+    }
+
+    #[test]
+    fn test_walk_frames() {
+        let trace = [
+            entry(0x1000, 0),
+            entry(0x2000, 10),
+            entry(0x3000, 20),
+            entry(0x2000, 2),
+            entry(0x2000, 3),
+        ];
+        let mut depths = Vec::new();
+        let frames = walk_frames(&trace, |pc| pc, |frames, _| depths.push(frames.len()));
+        assert_eq!(frames, vec![0, 10]);
+        assert_eq!(depths, vec![1, 2, 3, 2, 2]);
+
+        assert!(walk_frames(&[], |pc| pc, |_, _| {}).is_empty());
+    }
+}
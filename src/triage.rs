@@ -0,0 +1,710 @@
+//! Fuzz finding triage.
+//!
+//! Fuzz artifacts are re-run through a harness and classified by how they
+//! fail, then grouped by signature, so that artifacts reaching the same
+//! underlying bug are reported once, with the smallest as a representative.
+//!
+//! Panics are caught with a panic hook, installed on first use, which
+//! records the location of panics on a thread classifying an artifact instead
+//! of printing them. Panics on other threads are passed to the hook installed
+//! before it. Replacing the hook afterwards leaves panic locations unknown.
+
+use {
+    crate::{
+        fixture::{context::FixtureContext, effects::EffectsMismatch, Fixture},
+        harness::Harness,
+        result::ExecutionResult,
+    },
+    solana_sdk::{account::ReadableAccount, system_instruction::MAX_PERMITTED_DATA_LENGTH},
+    std::{
+        cell::{Cell, RefCell},
+        fmt, fs, io,
+        panic::{self, AssertUnwindSafe},
+        path::{Path, PathBuf},
+        sync::Once,
+    },
+};
+
+/// The class of a fuzz finding.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum FindingClass {
+    /// The harness itself panicked.
+    HarnessPanic,
+    /// The program runtime panicked.
+    RuntimePanic,
+    /// The execution violated a runtime invariant, such as lamports being
+    /// conserved.
+    InvariantViolation,
+    /// The execution's effects don't match the fixture's expected effects.
+    ExpectationMismatch,
+}
+
+/// The signature of a fuzz finding, shared by findings of the same
+/// underlying bug.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FindingSignature {
+    /// The class of the finding.
+    pub class: FindingClass,
+    /// What distinguishes findings of the class: the panic location, the
+    /// violated invariant, or the mismatch with the resulting error code and
+    /// faulting program counter, if known.
+    pub detail: String,
+}
+
+impl fmt::Display for FindingSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.class, self.detail)
+    }
+}
+
+/// Fuzz artifacts sharing a signature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FindingGroup {
+    /// The shared signature.
+    pub signature: FindingSignature,
+    /// The smallest artifact of the group.
+    pub representative: PathBuf,
+    /// A description of the representative's failure, such as the panic
+    /// message.
+    pub message: String,
+    /// Every artifact of the group.
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// The report of a fuzz finding triage.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TriageReport {
+    /// The groups of findings, ordered by class and signature.
+    pub groups: Vec<FindingGroup>,
+    /// Artifacts which execute without failing.
+    pub passing: Vec<PathBuf>,
+    /// Artifacts which aren't valid fixtures, or whose program can't be
+    /// loaded from their accounts.
+    pub invalid: Vec<PathBuf>,
+}
+
+impl fmt::Display for TriageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.groups {
+            writeln!(
+                f,
+                "{} ({} artifacts)\n  representative: {}\n  {}",
+                group.signature,
+                group.artifacts.len(),
+                group.representative.display(),
+                group.message,
+            )?;
+        }
+        writeln!(
+            f,
+            "{} classes, {} passing, {} invalid",
+            self.groups.len(),
+            self.passing.len(),
+            self.invalid.len(),
+        )
+    }
+}
+
+/// The outcome of re-running one artifact.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Classification {
+    /// The artifact failed, with a signature and a description.
+    Finding(FindingSignature, String),
+    /// The artifact executes without failing.
+    Passing,
+    /// The artifact isn't a valid fixture, or its program can't be loaded
+    /// from its accounts.
+    Invalid,
+}
+
+thread_local! {
+    // Whether panics on this thread are recorded rather than reported.
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+    // The location and message of the last panic recorded on this thread.
+    static LAST_PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+// Install the panic hook recording panics on threads classifying artifacts,
+// passing other panics to the previous hook.
+fn install_panic_hook() {
+    INSTALL_PANIC_HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !RECORDING.with(Cell::get) {
+                return previous_hook(info);
+            }
+            let location = info
+                .location()
+                .map(|location| {
+                    format!(
+                        "{}:{}:{}",
+                        location.file(),
+                        location.line(),
+                        location.column()
+                    )
+                })
+                .unwrap_or_else(|| String::from("unknown location"));
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            LAST_PANIC.with(|last_panic| last_panic.replace(Some((location, message))));
+        }));
+    });
+}
+
+// Run `f`, catching any panic on this thread and returning its location and
+// message instead of reporting it.
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, (String, String)> {
+    install_panic_hook();
+    let was_recording = RECORDING.with(|recording| recording.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    RECORDING.with(|recording| recording.set(was_recording));
+    result.map_err(|_| {
+        LAST_PANIC
+            .with(|last_panic| last_panic.take())
+            .unwrap_or_else(|| (String::from("unknown location"), String::new()))
+    })
+}
+
+// Whether a panic location is in one of the program runtime's crates, such
+// as `solana-program-runtime` or `solana_rbpf`, rather than in the harness.
+// Crates are identified by the directory containing their `src` directory,
+// which is suffixed with the crate's version in the Cargo registry.
+fn is_runtime_location(file: &str) -> bool {
+    let components = Path::new(file)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    components.windows(2).any(|pair| {
+        let directory = pair[0].as_ref();
+        let name = directory
+            .match_indices('-')
+            .find(|(index, _)| directory[index + 1..].starts_with(|c: char| c.is_ascii_digit()))
+            .map_or(directory, |(index, _)| &directory[..index]);
+        pair[1] == "src" && (name.starts_with("solana-") || name == "solana_rbpf")
+    })
+}
+
+// The finding for a panic while executing a fixture, by where it panicked.
+fn panic_finding(location: String, message: String) -> Classification {
+    let class = if is_runtime_location(&location) {
+        FindingClass::RuntimePanic
+    } else {
+        FindingClass::HarnessPanic
+    };
+    Classification::Finding(
+        FindingSignature {
+            class,
+            detail: format!("panicked at {}", location),
+        },
+        message,
+    )
+}
+
+// The runtime invariant an execution violates, if any.
+fn violated_invariant(context: &FixtureContext, result: &ExecutionResult) -> Option<String> {
+    if result.result.is_ok() {
+        let input_lamports = |pubkey| {
+            context
+                .accounts
+                .iter()
+                .find(|(key, _)| key == pubkey)
+                .map_or(0, |(_, account)| account.lamports() as u128)
+        };
+        let (before, after) = result.resulting_accounts.iter().fold(
+            (0u128, 0u128),
+            |(before, after), (pubkey, account)| {
+                (
+                    before + input_lamports(pubkey),
+                    after + account.lamports() as u128,
+                )
+            },
+        );
+        if before != after {
+            return Some(String::from("lamports not conserved"));
+        }
+    }
+    result
+        .resulting_accounts
+        .iter()
+        .any(|(_, account)| account.data().len() as u64 > MAX_PERMITTED_DATA_LENGTH)
+        .then(|| String::from("account data length exceeds the maximum"))
+}
+
+// The signature detail of an expectation mismatch: the mismatch kind, the
+// resulting error code, and the faulting program counter if a backtrace was
+// captured.
+fn mismatch_detail(mismatch: &EffectsMismatch, result: &ExecutionResult) -> String {
+    let kind = match mismatch {
+        EffectsMismatch::Result { .. } => "result",
+        EffectsMismatch::Account { .. } => "account",
+        EffectsMismatch::ReturnData { .. } => "return data",
+        EffectsMismatch::InnerInstructions { .. } => "inner instructions",
+        EffectsMismatch::ModifiedPrograms { .. } => "modified programs",
+    };
+    let mut detail = format!("{} mismatch, got {:?}", kind, result.result);
+    if let Some(frame) = result
+        .backtrace
+        .as_ref()
+        .and_then(|backtrace| backtrace.frames.first())
+    {
+        detail.push_str(&format!(" at {:#x}", frame.pc));
+    }
+    detail
+}
+
+/// Re-run a fixture blob through the harness and classify it. Without an
+/// ELF, the program is loaded from the fixture's accounts.
+pub fn classify(harness: &Harness, blob: &[u8], elf: Option<&[u8]>) -> Classification {
+    let Ok(Fixture { input, output }) = Fixture::decode(blob) else {
+        return Classification::Invalid;
+    };
+    let context = input.clone();
+    let execution = catch_panic(|| match elf {
+        Some(elf) => Some(harness.execute_instruction(context, elf)),
+        None => harness.execute_instruction_from_accounts(context).ok(),
+    });
+    let result = match execution {
+        Ok(Some(result)) => result,
+        Ok(None) => return Classification::Invalid,
+        Err((location, message)) => return panic_finding(location, message),
+    };
+
+    if let Some(invariant) = violated_invariant(&input, &result) {
+        return Classification::Finding(
+            FindingSignature {
+                class: FindingClass::InvariantViolation,
+                detail: invariant,
+            },
+            format!(
+                "{} with {:?}",
+                harness.describe_result(&result.result),
+                result.resulting_accounts
+            ),
+        );
+    }
+    match output.mismatch(&result) {
+        Some(mismatch) => Classification::Finding(
+            FindingSignature {
+                class: FindingClass::ExpectationMismatch,
+                detail: mismatch_detail(&mismatch, &result),
+            },
+            format!("{:?}", mismatch),
+        ),
+        None => Classification::Passing,
+    }
+}
+
+/// Re-run fuzz artifacts, fixture blobs such as those written by
+/// `cargo fuzz`, through the harness, and group the failing ones by
+/// signature. Without an ELF, each program is loaded from the fixture's
+/// accounts.
+///
+/// Enable backtraces on the harness to distinguish mismatches by faulting
+/// program counter.
+pub fn triage_artifacts(
+    harness: &Harness,
+    artifacts: &[PathBuf],
+    elf: Option<&[u8]>,
+) -> io::Result<TriageReport> {
+    let classifications = artifacts
+        .iter()
+        .map(|path| {
+            let blob = fs::read(path)?;
+            Ok((path, blob.len(), classify(harness, &blob, elf)))
+        })
+        .collect::<io::Result<Vec<_>>>();
+
+    let mut report = TriageReport::default();
+    // Findings by signature, with the size of the representative.
+    let mut groups: Vec<(FindingGroup, usize)> = Vec::new();
+    for (path, size, classification) in classifications? {
+        match classification {
+            Classification::Passing => report.passing.push(path.clone()),
+            Classification::Invalid => report.invalid.push(path.clone()),
+            Classification::Finding(signature, message) => {
+                match groups
+                    .iter_mut()
+                    .find(|(group, _)| group.signature == signature)
+                {
+                    Some((group, representative_size)) => {
+                        group.artifacts.push(path.clone());
+                        if (size, path.as_path()) < (*representative_size, &group.representative) {
+                            group.representative = path.clone();
+                            group.message = message;
+                            *representative_size = size;
+                        }
+                    }
+                    None => groups.push((
+                        FindingGroup {
+                            signature,
+                            representative: path.clone(),
+                            message,
+                            artifacts: vec![path.clone()],
+                        },
+                        size,
+                    )),
+                }
+            }
+        }
+    }
+    report.groups = groups.into_iter().map(|(group, _)| group).collect();
+    report.groups.sort_by(|a, b| a.signature.cmp(&b.signature));
+    Ok(report)
+}
+
+/// The fuzz artifacts in a directory, such as `fuzz/artifacts/<target>`.
+pub fn artifacts_in(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut artifacts = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .filter(|path| path.as_ref().map_or(true, |path| path.is_file()))
+        .collect::<io::Result<Vec<_>>>()?;
+    artifacts.sort();
+    Ok(artifacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            backtrace::{Backtrace, BacktraceFrame},
+            fixture::{context::ProgramStatus, proto, sysvars::FixtureSysvarContext},
+        },
+        prost::Message,
+        solana_sdk::{
+            account::{Account, AccountSharedData},
+            bpf_loader_upgradeable,
+            feature_set::FeatureSet,
+            instruction::InstructionError,
+            pubkey::Pubkey,
+            system_instruction, system_program,
+        },
+        std::thread,
+    };
+
+    fn acct_state(pubkey: &Pubkey, lamports: u64, data: Vec<u8>) -> proto::AcctState {
+        proto::AcctState {
+            address: pubkey.to_bytes().to_vec(),
+            lamports,
+            data,
+            owner: system_program::id().to_bytes().to_vec(),
+            ..proto::AcctState::default()
+        }
+    }
+
+    // A fixture transferring lamports from a funded system account to an
+    // empty one, expecting the recipient to end with `expected_lamports`.
+    fn transfer_fixture(lamports: u64, expected_lamports: u64) -> proto::InstrFixture {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instruction = system_instruction::transfer(&sender, &recipient, lamports);
+        proto::InstrFixture {
+            input: Some(proto::InstrContext {
+                program_id: system_program::id().to_bytes().to_vec(),
+                loader_id: solana_sdk::native_loader::id().to_bytes().to_vec(),
+                accounts: vec![
+                    acct_state(&sender, 1_000, vec![]),
+                    acct_state(&recipient, 0, vec![]),
+                ],
+                instr_accounts: vec![
+                    proto::InstrAcct {
+                        index: 0,
+                        is_signer: true,
+                        is_writable: true,
+                    },
+                    proto::InstrAcct {
+                        index: 1,
+                        is_signer: false,
+                        is_writable: true,
+                    },
+                ],
+                data: instruction.data,
+                ..proto::InstrContext::default()
+            }),
+            output: Some(proto::InstrEffects {
+                modified_accounts: vec![acct_state(&recipient, expected_lamports, vec![])],
+                ..proto::InstrEffects::default()
+            }),
+        }
+    }
+
+    // Add an account which the instruction doesn't use to a fixture.
+    fn with_unused_account(
+        mut fixture: proto::InstrFixture,
+        data_len: usize,
+    ) -> proto::InstrFixture {
+        let input = fixture.input.as_mut().unwrap();
+        input
+            .accounts
+            .push(acct_state(&Pubkey::new_unique(), 1, vec![0; data_len]));
+        fixture
+    }
+
+    fn account(lamports: u64) -> AccountSharedData {
+        AccountSharedData::from(Account {
+            lamports,
+            ..Account::default()
+        })
+    }
+
+    fn execution_result(
+        result: Result<(), InstructionError>,
+        resulting_accounts: Vec<(Pubkey, AccountSharedData)>,
+    ) -> ExecutionResult {
+        ExecutionResult {
+            result,
+            compute_units_consumed: 100,
+            resulting_accounts,
+            program_load_error: None,
+            modified_programs: vec![],
+            return_data: None,
+            inner_instructions: vec![],
+            backtrace: None,
+        }
+    }
+
+    #[test]
+    fn test_violated_invariant() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let context = FixtureContext {
+            program_id: Pubkey::new_unique(),
+            loader_id: bpf_loader_upgradeable::id(),
+            feature_set: FeatureSet::all_enabled(),
+            sysvar_context: FixtureSysvarContext::default(),
+            accounts: vec![(first, account(100)), (second, account(0))],
+            instruction_accounts: vec![],
+            instruction_data: vec![],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
+        };
+
+        let transferred = vec![(first, account(40)), (second, account(60))];
+        assert_eq!(
+            violated_invariant(&context, &execution_result(Ok(()), transferred)),
+            None
+        );
+
+        let minted = vec![(first, account(100)), (second, account(60))];
+        assert_eq!(
+            violated_invariant(&context, &execution_result(Ok(()), minted.clone())),
+            Some(String::from("lamports not conserved"))
+        );
+        // Only successful instructions are checked.
+        assert_eq!(
+            violated_invariant(
+                &context,
+                &execution_result(Err(InstructionError::Custom(1)), minted)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mismatch_detail() {
+        let mismatch = EffectsMismatch::Result {
+            expected: Ok(()),
+            actual: Err(InstructionError::Custom(6)),
+        };
+        let mut result = execution_result(Err(InstructionError::Custom(6)), vec![]);
+        assert_eq!(
+            mismatch_detail(&mismatch, &result),
+            "result mismatch, got Err(Custom(6))"
+        );
+
+        // The expected state doesn't distinguish findings, the faulting
+        // program counter does.
+        result.backtrace = Some(Backtrace {
+            panic_location: None,
+            frames: vec![BacktraceFrame {
+                pc: 0x1a,
+                function: String::from("entrypoint"),
+                file: None,
+                line: None,
+            }],
+        });
+        let mismatch = EffectsMismatch::Account {
+            pubkey: Pubkey::new_unique(),
+            expected: account(1),
+            actual: account(2),
+        };
+        assert_eq!(
+            mismatch_detail(&mismatch, &result),
+            "account mismatch, got Err(Custom(6)) at 0x1a"
+        );
+    }
+
+    #[test]
+    fn test_triage_invalid_artifacts() {
+        let directory = std::env::temp_dir().join(format!("triage-{}", Pubkey::new_unique()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("crash-1"), [0xff; 8]).unwrap();
+        fs::write(directory.join("crash-2"), [0x0a, 0x02, 0x0a]).unwrap();
+
+        let artifacts = artifacts_in(&directory).unwrap();
+        let report = triage_artifacts(&Harness::default(), &artifacts, None).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(report.invalid, artifacts);
+        assert!(report.groups.is_empty());
+        assert!(report.passing.is_empty());
+        assert_eq!(report.to_string(), "0 classes, 0 passing, 2 invalid\n");
+    }
+
+    #[test]
+    fn test_is_runtime_location() {
+        let registry = "/home/dev/.cargo/registry/src/index.crates.io-6f17d22bba15001f";
+        assert!(is_runtime_location(&format!(
+            "{}/solana-program-1.18.26/src/rent.rs",
+            registry
+        )));
+        assert!(is_runtime_location(&format!(
+            "{}/solana_rbpf-0.8.0/src/vm.rs",
+            registry
+        )));
+        assert!(!is_runtime_location("src/triage.rs"));
+        assert!(!is_runtime_location(&format!(
+            "{}/bincode-1.3.3/src/de/mod.rs",
+            registry
+        )));
+        // Harness paths merely containing a runtime crate's prefix.
+        assert!(!is_runtime_location(
+            "/home/dev/solana-fuzzing/protosol/src/harness.rs"
+        ));
+        assert!(!is_runtime_location(
+            "/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/core/src/panicking.rs"
+        ));
+    }
+
+    #[test]
+    fn test_catch_panic_other_threads() {
+        let result = catch_panic(|| {
+            // Panics on other threads are reported as usual, not recorded.
+            let recorded = thread::spawn(|| {
+                let _ = panic::catch_unwind(|| panic!("Other thread panicked"));
+                LAST_PANIC.with(|last_panic| last_panic.take())
+            })
+            .join()
+            .unwrap();
+            assert_eq!(recorded, None);
+            panic!("Triaging thread panicked");
+        });
+        let (location, message) = result.unwrap_err();
+        assert!(location.starts_with("src/triage.rs:"));
+        assert_eq!(message, "Triaging thread panicked");
+        assert!(!RECORDING.with(Cell::get));
+    }
+
+    #[test]
+    fn test_panic_finding() {
+        let Classification::Finding(signature, message) = panic_finding(
+            String::from("src/programs_cache.rs:1:2"),
+            String::from("Harness panicked"),
+        ) else {
+            panic!("Expected a finding");
+        };
+        assert_eq!(signature.class, FindingClass::HarnessPanic);
+        assert_eq!(signature.detail, "panicked at src/programs_cache.rs:1:2");
+        assert_eq!(message, "Harness panicked");
+
+        let Classification::Finding(signature, _) = panic_finding(
+            String::from(
+                "/root/.cargo/registry/src/index.crates.io-6f17d22bba15001f/\
+                 solana-program-runtime-1.18.26/src/invoke_context.rs:1:2",
+            ),
+            String::from("Runtime panicked"),
+        ) else {
+            panic!("Expected a finding");
+        };
+        assert_eq!(signature.class, FindingClass::RuntimePanic);
+    }
+
+    #[test]
+    fn test_triage_artifacts() {
+        let directory = std::env::temp_dir().join(format!("triage-{}", Pubkey::new_unique()));
+        fs::create_dir_all(&directory).unwrap();
+        let write = |name: &str, fixture: proto::InstrFixture| {
+            let path = directory.join(name);
+            fs::write(&path, fixture.encode_to_vec()).unwrap();
+            path
+        };
+
+        let passing = write("passing", transfer_fixture(10, 10));
+        // Both artifacts reach the same mismatch, and the smaller represents
+        // them.
+        let small_mismatch = write("mismatch-1", transfer_fixture(10, 11));
+        let large_mismatch = write(
+            "mismatch-0",
+            with_unused_account(transfer_fixture(10, 11), 100),
+        );
+        let insufficient_funds = write("insufficient-funds", transfer_fixture(2_000, 2_000));
+        let oversized = write(
+            "oversized",
+            with_unused_account(
+                transfer_fixture(10, 10),
+                MAX_PERMITTED_DATA_LENGTH as usize + 1,
+            ),
+        );
+        // Rent overflows when the runtime computes a rent-exempt balance.
+        let mut fixture = transfer_fixture(10, 10);
+        fixture.input.as_mut().unwrap().sysvars = Some(proto::SysvarContext {
+            rent: Some(proto::Rent {
+                lamports_per_byte_year: u64::MAX,
+                ..proto::Rent::default()
+            }),
+            ..proto::SysvarContext::default()
+        });
+        let runtime_panic = write("runtime-panic", fixture);
+
+        let artifacts = artifacts_in(&directory).unwrap();
+        let report = triage_artifacts(&Harness::default(), &artifacts, Some(&[])).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(report.passing, vec![passing]);
+        assert!(report.invalid.is_empty());
+        let groups = report
+            .groups
+            .iter()
+            .map(|group| {
+                (
+                    group.signature.class,
+                    group.representative.clone(),
+                    group.artifacts.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (FindingClass::RuntimePanic, runtime_panic, 1),
+                (FindingClass::InvariantViolation, oversized, 1),
+                (FindingClass::ExpectationMismatch, small_mismatch, 2),
+                (FindingClass::ExpectationMismatch, insufficient_funds, 1),
+            ]
+        );
+        assert!(report.groups[0]
+            .signature
+            .detail
+            .contains("solana-program-"));
+        assert_eq!(
+            report.groups[1].signature.detail,
+            "account data length exceeds the maximum"
+        );
+        assert_eq!(
+            report.groups[2].signature.detail,
+            "account mismatch, got Ok(())"
+        );
+        assert!(report.groups[2].artifacts.contains(&large_mismatch));
+        assert_eq!(
+            report.groups[3].signature.detail,
+            "result mismatch, got Err(Custom(1))"
+        );
+    }
+}
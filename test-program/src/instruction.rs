@@ -27,6 +27,12 @@ pub enum TestProgramInstruction {
     /// 1. `[writable]` The system account recipient.
     /// 2. `[]`         The System program.
     TransferWithCpi { amount: u64 },
+    /// Set the remaining instruction data as the program's return data.
+    /// No accounts.
+    SetReturnData { data: Vec<u8> },
+    /// Panic.
+    /// No accounts.
+    Panic,
 }
 
 impl TestProgramInstruction {
@@ -61,6 +67,10 @@ impl TestProgramInstruction {
                     .ok_or(ProgramError::InvalidInstructionData)?;
                 Self::TransferWithCpi { amount }
             }
+            6 => Self::SetReturnData {
+                data: rest.to_vec(),
+            },
+            7 => Self::Panic,
             _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
@@ -9,7 +9,7 @@ use {
         account_info::{next_account_info, AccountInfo},
         clock::Clock,
         entrypoint::ProgramResult,
-        program::invoke,
+        program::{invoke, set_return_data},
         program_error::ProgramError,
         pubkey::Pubkey,
         sysvar::Sysvar,
@@ -114,7 +114,16 @@ fn process_transfer_with_cpi(accounts: &[AccountInfo], amount: u64) -> ProgramRe
     Ok(())
 }
 
-fn process_instruction(
+fn process_set_return_data(data: &[u8]) -> ProgramResult {
+    set_return_data(data);
+    Ok(())
+}
+
+fn process_panic() -> ProgramResult {
+    panic!("Panic instruction");
+}
+
+pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     input: &[u8],
@@ -133,5 +142,7 @@ fn process_instruction(
         TestProgramInstruction::TransferWithCpi { amount } => {
             process_transfer_with_cpi(accounts, amount)
         }
+        TestProgramInstruction::SetReturnData { data } => process_set_return_data(&data),
+        TestProgramInstruction::Panic => process_panic(),
     }
 }
@@ -0,0 +1,68 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::{
+        account_substitution::{run_account_substitution_scan, AccountSubstitution},
+        harness::Harness,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+#[test]
+fn test_account_substitution_scan() {
+    let pubkey = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    let context = test_context(
+        vec![
+            (
+                pubkey,
+                AccountSharedData::from(Account {
+                    data: vec![1, 2, 3, 4],
+                    lamports: 100_000_000,
+                    owner: test_program::id(),
+                    ..Account::default()
+                }),
+            ),
+            (destination, AccountSharedData::default()),
+        ],
+        vec![
+            AccountMeta::new(pubkey, false),
+            AccountMeta::new(destination, false),
+        ],
+        vec![3], // CloseAccount
+    );
+
+    let report =
+        run_account_substitution_scan(&Harness::default(), &context, get_test_elf()).unwrap();
+    assert_eq!(report.baseline.result, Ok(()));
+    assert_eq!(report.substitutions_executed, 5);
+
+    // The closed account's owner is checked, so only the destination's
+    // owner, and impostors of either account, go unnoticed.
+    let accepted = report
+        .accepted_substitutions
+        .iter()
+        .map(|accepted| accepted.substitution.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(accepted.len(), 3);
+    assert_eq!(
+        accepted[0],
+        AccountSubstitution::Owner {
+            pubkey: destination
+        }
+    );
+    assert!(matches!(
+        accepted[1],
+        AccountSubstitution::Impostor { pubkey: impostor_of, .. } if impostor_of == pubkey
+    ));
+    assert!(matches!(
+        accepted[2],
+        AccountSubstitution::Impostor { pubkey: impostor_of, .. } if impostor_of == destination
+    ));
+}
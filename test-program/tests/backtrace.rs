@@ -0,0 +1,42 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::harness::Harness,
+    solana_sdk::instruction::InstructionError,
+};
+
+#[test]
+fn test_backtrace_panic() {
+    let harness = Harness::default().with_backtraces();
+    let context = test_context(vec![], vec![], vec![7]); // Panic
+    let result = harness.execute_instruction(context, get_test_elf());
+    assert_eq!(
+        result.result,
+        Err(InstructionError::ProgramFailedToComplete)
+    );
+
+    let backtrace = result.backtrace.unwrap();
+    let panic_location = backtrace.panic_location.as_ref().unwrap();
+    assert!(panic_location.file.ends_with("src/lib.rs"));
+    assert!(backtrace
+        .frames
+        .iter()
+        .any(|frame| frame.function.contains("process_instruction")));
+}
+
+#[test]
+fn test_backtrace_success() {
+    let harness = Harness::default().with_backtraces();
+    let context = test_context(vec![], vec![], vec![6]); // SetReturnData
+    let result = harness.execute_instruction(context, get_test_elf());
+    assert_eq!(result.result, Ok(()));
+    assert_eq!(result.backtrace, None);
+}
+
+#[test]
+fn test_backtrace_disabled() {
+    let result = Harness::default()
+        .execute_instruction(test_context(vec![], vec![], vec![7]), get_test_elf());
+    assert_eq!(result.backtrace, None);
+}
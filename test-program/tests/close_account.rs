@@ -3,12 +3,15 @@ mod common;
 use {
     common::process_fixture,
     protosol::fixture::{
-        context::FixtureContext, effects::FixtureEffects, sysvars::FixtureSysvarContext, Fixture,
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        sysvars::FixtureSysvarContext,
+        Fixture,
     },
     solana_sdk::{
         account::{Account, AccountSharedData},
         feature_set::FeatureSet,
-        instruction::AccountMeta,
+        instruction::{AccountMeta, InstructionError},
         pubkey::Pubkey,
     },
     std::vec,
@@ -62,11 +65,16 @@ fn test_write_data() {
             instruction_data: vec![
                 3, // CloseAccount
             ],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: 0,
-            custom_error: 0,
+            result: Ok(()),
             modified_accounts,
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -107,11 +115,16 @@ fn test_close_account_fail_bad_owner() {
             instruction_data: vec![
                 2, // WriteClockData
             ],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: -1,                // -1 for failure
-            custom_error: 30064771072, // `ProgramError::IncorrectProgramId`
+            result: Err(InstructionError::IncorrectProgramId),
             modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
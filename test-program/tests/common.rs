@@ -1,11 +1,55 @@
-use protosol::fixture::Fixture;
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use {
+    protosol::fixture::{
+        context::{FixtureContext, ProgramStatus},
+        sysvars::FixtureSysvarContext,
+        Fixture,
+    },
+    solana_sdk::{
+        account::AccountSharedData, feature_set::FeatureSet, instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
 
 // Perhaps this can be macro-ized.
-fn get_test_elf<'a>() -> &'a [u8] {
+pub fn get_test_elf<'a>() -> &'a [u8] {
     include_bytes!("../target/deploy/test_program.so")
 }
 
+// A context for an instruction to the test program, deployed by the
+// upgradeable loader with every feature enabled. Tests override other
+// fields with struct update syntax.
+pub fn test_context(
+    accounts: Vec<(Pubkey, AccountSharedData)>,
+    instruction_accounts: Vec<AccountMeta>,
+    instruction_data: Vec<u8>,
+) -> FixtureContext {
+    FixtureContext {
+        program_id: test_program::id(),
+        loader_id: solana_sdk::bpf_loader_upgradeable::id(),
+        feature_set: FeatureSet::all_enabled(),
+        sysvar_context: FixtureSysvarContext::default(),
+        accounts,
+        instruction_accounts,
+        instruction_data,
+        program_status: ProgramStatus::Deployed,
+        upgrade_authority: None,
+        deployment_slot: None,
+    }
+}
+
 pub fn process_fixture(fixture: Fixture) {
     let elf = get_test_elf();
     protosol::process_fixture(fixture, elf);
 }
+
+pub fn process_fixture_native(fixture: Fixture) {
+    protosol::process_fixture_native(fixture, test_program::process_instruction);
+}
+
+pub fn process_fixture_differential(fixture: Fixture) {
+    let elf = get_test_elf();
+    protosol::process_fixture_differential(fixture, elf, test_program::process_instruction);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::{
+        fixture::{effects::FixtureEffects, Fixture},
+        harness::Harness,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+fn create_fixture(data: [u8; 4]) -> Fixture {
+    let pubkey = Pubkey::new_unique();
+    let account = |data: Vec<u8>| {
+        AccountSharedData::from(Account {
+            lamports: 100_000_000,
+            data,
+            owner: test_program::id(),
+            ..Account::default()
+        })
+    };
+
+    let mut instruction_data = vec![1]; // WriteData
+    instruction_data.extend_from_slice(&data);
+
+    Fixture {
+        input: test_context(
+            vec![(pubkey, account(vec![0; 4]))],
+            vec![AccountMeta::new(pubkey, false)],
+            instruction_data,
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, account(data.to_vec()))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    }
+}
+
+#[test]
+fn test_coverage() {
+    let elf = get_test_elf();
+    let harness = Harness::default().with_coverage();
+
+    harness.process_fixture(create_fixture([1, 2, 3, 4]), elf);
+    let coverage = harness.coverage().unwrap();
+    assert!(!coverage.program_counters.is_empty());
+
+    // Coverage accumulates across fixtures.
+    harness.process_fixture(create_fixture([5, 6, 7, 8]), elf);
+    let accumulated = harness.coverage().unwrap();
+    for (pc, count) in &coverage.program_counters {
+        assert_eq!(accumulated.program_counters[pc], count * 2);
+    }
+
+    let mut report = Vec::new();
+    accumulated.write_lcov(elf, &mut report).unwrap();
+}
+
+#[test]
+fn test_coverage_disabled() {
+    let harness = Harness::default();
+    harness.process_fixture(create_fixture([1, 2, 3, 4]), get_test_elf());
+    assert_eq!(harness.coverage(), None);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::{
+        fixture::{effects::FixtureEffects, Fixture},
+        harness::Harness,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+    std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+        time::Duration,
+    },
+};
+
+const DEBUG_PORT: u16 = 21212;
+
+// A minimal GDB remote protocol client.
+struct GdbClient(TcpStream);
+
+impl GdbClient {
+    fn connect(port: u16) -> Self {
+        for _ in 0..100 {
+            if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+                return Self(stream);
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Debug server not listening on port {}", port);
+    }
+
+    fn request(&mut self, command: &str) -> String {
+        let checksum = command
+            .bytes()
+            .fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.0, "${}#{:02x}", command, checksum).unwrap();
+
+        // Skip acknowledgements up to the reply packet, then acknowledge it.
+        let mut reply: Vec<u8> = Vec::new();
+        let mut byte = [0];
+        while byte[0] != b'$' {
+            self.0.read_exact(&mut byte).unwrap();
+        }
+        loop {
+            self.0.read_exact(&mut byte).unwrap();
+            match byte[0] {
+                b'#' => break,
+                // Run-length encoding repeats the previous byte.
+                b'*' => {
+                    self.0.read_exact(&mut byte).unwrap();
+                    let previous = *reply.last().unwrap();
+                    reply.extend(std::iter::repeat_n(previous, byte[0] as usize - 29));
+                }
+                byte => reply.push(byte),
+            }
+        }
+        self.0.read_exact(&mut [0; 2]).unwrap();
+        self.0.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+#[test]
+fn test_debugger() {
+    let pubkey = Pubkey::new_unique();
+    let account = |data: Vec<u8>| {
+        AccountSharedData::from(Account {
+            lamports: 100_000_000,
+            data,
+            owner: test_program::id(),
+            ..Account::default()
+        })
+    };
+
+    let mut instruction_data = vec![1]; // WriteData
+    instruction_data.extend_from_slice(&[1, 2, 3, 4]);
+
+    let fixture = Fixture {
+        input: test_context(
+            vec![(pubkey, account(vec![0; 4]))],
+            vec![AccountMeta::new(pubkey, false)],
+            instruction_data,
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, account(vec![1, 2, 3, 4]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    let execution = thread::spawn(move || {
+        Harness::default()
+            .with_debug_port(DEBUG_PORT)
+            .process_fixture(fixture, get_test_elf());
+    });
+
+    let mut client = GdbClient::connect(DEBUG_PORT);
+    // Stopped at the entrypoint, with the input region in r1.
+    let registers = client.request("g");
+    assert_eq!(&registers[16..32], "0000000004000000");
+    // Stopped by SIGTRAP after a single step.
+    assert_eq!(client.request("s"), "S05");
+    // Exited with status 0.
+    assert_eq!(client.request("c"), "W00");
+
+    execution.join().unwrap();
+}
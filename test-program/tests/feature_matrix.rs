@@ -0,0 +1,64 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::feature_matrix::{run_feature_matrix, FeatureMatrixMode},
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        feature_set::{bpf_account_data_direct_mapping, loosen_cpi_size_restriction},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+#[test]
+fn test_feature_matrix_write_data() {
+    let pubkey = Pubkey::new_unique();
+
+    let account_inputs = vec![(
+        pubkey,
+        AccountSharedData::from(Account {
+            data: vec![0; 4],
+            lamports: 100_000_000,
+            owner: test_program::id(),
+            ..Account::default()
+        }),
+    )];
+
+    let instruction_accounts = vec![AccountMeta::new(pubkey, false)];
+
+    let instruction_data = vec![
+        1, // WriteData
+        7, 7, 7, 7, // Data to write
+    ];
+
+    let context = test_context(account_inputs, instruction_accounts, instruction_data);
+
+    let features = &[
+        bpf_account_data_direct_mapping::id(),
+        loosen_cpi_size_restriction::id(),
+    ];
+
+    // Writing data should not depend on either feature.
+    let report = run_feature_matrix(
+        &context,
+        features,
+        FeatureMatrixMode::Toggle,
+        get_test_elf(),
+    )
+    .unwrap();
+    assert!(report.baseline.result.is_ok());
+    assert_eq!(report.variants_executed, 2);
+    assert!(report.divergent_variants.is_empty());
+
+    let report = run_feature_matrix(
+        &context,
+        features,
+        FeatureMatrixMode::Combinations,
+        get_test_elf(),
+    )
+    .unwrap();
+    assert!(report.baseline.result.is_ok());
+    assert_eq!(report.variants_executed, 3);
+    assert!(report.divergent_variants.is_empty());
+}
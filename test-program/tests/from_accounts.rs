@@ -0,0 +1,151 @@
+mod common;
+
+use {
+    common::get_test_elf,
+    protosol::fixture::{
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        error::FixtureError,
+        sysvars::FixtureSysvarContext,
+        Fixture,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        bpf_loader_upgradeable::UpgradeableLoaderState,
+        feature_set::FeatureSet,
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+};
+
+fn write_data_fixture(program_accounts: Vec<(Pubkey, AccountSharedData)>) -> Fixture {
+    let pubkey = Pubkey::new_unique();
+
+    let account = AccountSharedData::from(Account {
+        data: vec![0; 4],
+        lamports: 100_000_000,
+        owner: test_program::id(),
+        ..Account::default()
+    });
+
+    let modified_account = AccountSharedData::from(Account {
+        data: vec![7, 7, 7, 7], // Data should be written.
+        lamports: 100_000_000,
+        owner: test_program::id(),
+        ..Account::default()
+    });
+
+    // Program accounts are listed after the instruction's accounts, as in
+    // captured fixtures.
+    let accounts = std::iter::once((pubkey, account))
+        .chain(program_accounts)
+        .collect();
+
+    Fixture {
+        input: FixtureContext {
+            program_id: test_program::id(),
+            // The loader is taken from the program account.
+            loader_id: Pubkey::default(),
+            feature_set: FeatureSet::all_enabled(),
+            sysvar_context: FixtureSysvarContext::default(),
+            accounts,
+            instruction_accounts: vec![AccountMeta::new(pubkey, false)],
+            instruction_data: vec![
+                1, // WriteData
+                7, 7, 7, 7, // Data to write
+            ],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
+        },
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, modified_account)],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    }
+}
+
+#[test]
+fn test_from_accounts_bpf_loader() {
+    let program_account = AccountSharedData::from(Account {
+        data: get_test_elf().to_vec(),
+        lamports: 100_000_000,
+        owner: solana_sdk::bpf_loader::id(),
+        executable: true,
+        ..Account::default()
+    });
+
+    let fixture = write_data_fixture(vec![(test_program::id(), program_account)]);
+
+    protosol::process_fixture_from_accounts(fixture).unwrap();
+}
+
+#[test]
+fn test_from_accounts_bpf_loader_upgradeable() {
+    let loader_id = solana_sdk::bpf_loader_upgradeable::id();
+    let programdata_address =
+        Pubkey::find_program_address(&[test_program::id().as_ref()], &loader_id).0;
+
+    let program_account = AccountSharedData::from(Account {
+        data: bincode::serialize(&UpgradeableLoaderState::Program {
+            programdata_address,
+        })
+        .unwrap(),
+        lamports: 100_000_000,
+        owner: loader_id,
+        executable: true,
+        ..Account::default()
+    });
+
+    let mut program_data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: None,
+    })
+    .unwrap();
+    program_data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+    program_data.extend_from_slice(get_test_elf());
+    let programdata_account = AccountSharedData::from(Account {
+        data: program_data,
+        lamports: 100_000_000,
+        owner: loader_id,
+        ..Account::default()
+    });
+
+    let fixture = write_data_fixture(vec![
+        (test_program::id(), program_account),
+        (programdata_address, programdata_account),
+    ]);
+
+    protosol::process_fixture_from_accounts(fixture).unwrap();
+}
+
+#[test]
+fn test_from_accounts_fail_missing_program_account() {
+    let fixture = write_data_fixture(vec![]);
+
+    assert_eq!(
+        protosol::process_fixture_from_accounts(fixture),
+        Err(FixtureError::InvalidProgramAccount)
+    );
+}
+
+#[test]
+fn test_from_accounts_fail_missing_instruction_account() {
+    let program_account = AccountSharedData::from(Account {
+        data: get_test_elf().to_vec(),
+        lamports: 100_000_000,
+        owner: solana_sdk::bpf_loader::id(),
+        executable: true,
+        ..Account::default()
+    });
+
+    let mut fixture = write_data_fixture(vec![(test_program::id(), program_account)]);
+    // The instruction's account is not among the input accounts.
+    fixture.input.accounts.remove(0);
+
+    let result = protosol::execute_instruction_from_accounts(fixture.input).unwrap();
+    assert_eq!(result.result, Err(InstructionError::MissingAccount));
+}
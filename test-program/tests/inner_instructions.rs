@@ -0,0 +1,98 @@
+mod common;
+
+use {
+    common::{process_fixture, process_fixture_native, test_context},
+    protosol::{
+        fixture::{
+            effects::{ExpectedInnerInstructions, FixtureEffects},
+            Fixture,
+        },
+        result::InnerInstruction,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+const BASE_LAMPORTS: u64 = 100_000_000;
+const TRANSFER_AMOUNT: u64 = 42_000;
+
+fn create_fixture(
+    inner_instructions: impl Fn(InnerInstruction) -> ExpectedInnerInstructions,
+) -> Fixture {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let system_account = |lamports| {
+        AccountSharedData::from(Account {
+            lamports,
+            owner: solana_sdk::system_program::id(),
+            ..Account::default()
+        })
+    };
+
+    let mut instruction_data = vec![5]; // TransferWithCpi
+    instruction_data.extend_from_slice(&TRANSFER_AMOUNT.to_le_bytes());
+
+    // The expected transfer depends on the generated accounts.
+    let transfer = InnerInstruction {
+        instruction: solana_sdk::system_instruction::transfer(&sender, &recipient, TRANSFER_AMOUNT),
+        stack_height: 2,
+    };
+
+    Fixture {
+        input: test_context(
+            vec![
+                (sender, system_account(BASE_LAMPORTS)),
+                (recipient, system_account(BASE_LAMPORTS)),
+            ],
+            vec![
+                AccountMeta::new(sender, true),
+                AccountMeta::new(recipient, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+            ],
+            instruction_data,
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![
+                (sender, system_account(BASE_LAMPORTS - TRANSFER_AMOUNT)),
+                (recipient, system_account(BASE_LAMPORTS + TRANSFER_AMOUNT)),
+            ],
+            return_data: None,
+            inner_instructions: Some(inner_instructions(transfer)),
+            modified_programs: None,
+        },
+    }
+}
+
+#[test]
+fn test_inner_instructions() {
+    process_fixture(create_fixture(|transfer| {
+        ExpectedInnerInstructions::Exact(vec![transfer])
+    }));
+}
+
+#[test]
+fn test_inner_instructions_native() {
+    process_fixture_native(create_fixture(|transfer| {
+        ExpectedInnerInstructions::Exact(vec![transfer])
+    }));
+}
+
+#[test]
+fn test_inner_instructions_prefix_native() {
+    process_fixture_native(create_fixture(|_| {
+        ExpectedInnerInstructions::Prefix(vec![])
+    }));
+}
+
+#[test]
+#[should_panic(expected = "Inner instructions mismatch")]
+fn test_inner_instructions_mismatch_native() {
+    process_fixture_native(create_fixture(|transfer| {
+        ExpectedInnerInstructions::Exact(vec![transfer.clone(), transfer])
+    }));
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use {
+    common::test_context,
+    protosol::{
+        fixture::{context::FixtureContext, effects::FixtureEffects, Fixture},
+        result::ProgramLoadError,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+};
+
+fn noop_context() -> FixtureContext {
+    let pubkey = Pubkey::new_unique();
+    test_context(
+        vec![(
+            pubkey,
+            AccountSharedData::from(Account {
+                lamports: 100_000_000,
+                owner: test_program::id(),
+                ..Account::default()
+            }),
+        )],
+        vec![AccountMeta::new_readonly(pubkey, false)],
+        vec![
+            0, // NoOp
+        ],
+    )
+}
+
+#[test]
+fn test_invalid_elf() {
+    let elf = b"not an elf";
+
+    // The program is tombstoned, so invoking it fails.
+    let fixture = Fixture {
+        input: noop_context(),
+        output: FixtureEffects {
+            result: Err(InstructionError::InvalidAccountData),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+    protosol::process_fixture(fixture, elf);
+
+    // The load error is reported with the result.
+    let result = protosol::execute_instruction(noop_context(), elf);
+    assert!(matches!(
+        result.program_load_error,
+        Some(ProgramLoadError::InvalidElf(_))
+    ));
+}
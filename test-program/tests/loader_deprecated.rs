@@ -0,0 +1,70 @@
+use {
+    protosol::fixture::{
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        sysvars::FixtureSysvarContext,
+        Fixture,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        feature_set::FeatureSet,
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+};
+
+// A C program from the BPF loader test ELFs, which deserializes its
+// parameters with the deprecated loader's unaligned layout and succeeds.
+fn noop_unaligned_elf<'a>() -> &'a [u8] {
+    include_bytes!("elfs/noop_unaligned.so")
+}
+
+fn noop_fixture(loader_id: Pubkey, result: Result<(), InstructionError>) -> Fixture {
+    let pubkey = Pubkey::new_unique();
+    Fixture {
+        input: FixtureContext {
+            program_id: test_program::id(),
+            loader_id,
+            feature_set: FeatureSet::all_enabled(),
+            sysvar_context: FixtureSysvarContext::default(),
+            accounts: vec![(
+                pubkey,
+                AccountSharedData::from(Account {
+                    lamports: 100_000_000,
+                    data: vec![1, 2, 3, 4],
+                    owner: test_program::id(),
+                    ..Account::default()
+                }),
+            )],
+            instruction_accounts: vec![AccountMeta::new(pubkey, false)],
+            instruction_data: vec![7, 7],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
+        },
+        output: FixtureEffects {
+            result,
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    }
+}
+
+#[test]
+fn test_unaligned_program() {
+    let fixture = noop_fixture(solana_sdk::bpf_loader_deprecated::id(), Ok(()));
+    protosol::process_fixture(fixture, noop_unaligned_elf());
+}
+
+#[test]
+fn test_unaligned_program_fail_aligned_serialization() {
+    // The upgradeable loader serializes the parameters aligned, which the
+    // program misreads.
+    let fixture = noop_fixture(
+        solana_sdk::bpf_loader_upgradeable::id(),
+        Err(InstructionError::ProgramFailedToComplete),
+    );
+    protosol::process_fixture(fixture, noop_unaligned_elf());
+}
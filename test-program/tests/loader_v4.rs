@@ -0,0 +1,109 @@
+mod common;
+
+use {
+    common::{process_fixture, test_context},
+    protosol::fixture::{
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        Fixture,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        feature_set::FeatureSet,
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+};
+
+fn noop_context(feature_set: FeatureSet, program_status: ProgramStatus) -> FixtureContext {
+    let pubkey = Pubkey::new_unique();
+    FixtureContext {
+        loader_id: solana_sdk::loader_v4::id(),
+        feature_set,
+        program_status,
+        ..test_context(
+            vec![(
+                pubkey,
+                AccountSharedData::from(Account {
+                    lamports: 100_000_000,
+                    owner: test_program::id(),
+                    ..Account::default()
+                }),
+            )],
+            vec![AccountMeta::new_readonly(pubkey, false)],
+            vec![
+                0, // NoOp
+            ],
+        )
+    }
+}
+
+// An SBPFv2 program from the loader v4 test ELFs, which returns the 8 bytes
+// of its `.rodata` section as its exit code.
+fn rodata_section_elf(exit_code: u8) -> Vec<u8> {
+    let mut elf = include_bytes!("elfs/rodata_section.so").to_vec();
+    // The `.rodata` section is at file offset 0x1020.
+    elf[0x1020] = exit_code;
+    elf
+}
+
+#[test]
+fn test_loader_v4_deployed_and_finalized() {
+    for program_status in [ProgramStatus::Deployed, ProgramStatus::Finalized] {
+        let fixture = Fixture {
+            input: noop_context(FeatureSet::all_enabled(), program_status),
+            output: FixtureEffects {
+                result: Ok(()),
+                modified_accounts: vec![],
+                return_data: None,
+                inner_instructions: None,
+                modified_programs: None,
+            },
+        };
+        protosol::process_fixture(fixture, &rodata_section_elf(0));
+
+        let fixture = Fixture {
+            input: noop_context(FeatureSet::all_enabled(), program_status),
+            output: FixtureEffects {
+                result: Err(InstructionError::Custom(42)),
+                modified_accounts: vec![],
+                return_data: None,
+                inner_instructions: None,
+                modified_programs: None,
+            },
+        };
+        protosol::process_fixture(fixture, &rodata_section_elf(42));
+    }
+}
+
+#[test]
+fn test_loader_v4_fail_retracted() {
+    let fixture = Fixture {
+        input: noop_context(FeatureSet::all_enabled(), ProgramStatus::Retracted),
+        output: FixtureEffects {
+            result: Err(InstructionError::InvalidArgument),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    process_fixture(fixture);
+}
+
+#[test]
+fn test_loader_v4_fail_feature_inactive() {
+    let fixture = Fixture {
+        input: noop_context(FeatureSet::default(), ProgramStatus::Retracted),
+        output: FixtureEffects {
+            result: Err(InstructionError::UnsupportedProgramId),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    process_fixture(fixture);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::{harness::Harness, mock_syscalls::MockSyscall},
+    solana_program_runtime::{
+        invoke_context::InvokeContext,
+        solana_rbpf::{declare_builtin_function, memory_region::MemoryMapping},
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData, ReadableAccount},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+declare_builtin_function!(
+    SyscallInvokeSignedNoop,
+    fn rust(
+        _invoke_context: &mut InvokeContext,
+        _instruction_addr: u64,
+        _account_infos_addr: u64,
+        _account_infos_len: u64,
+        _signers_seeds_addr: u64,
+        _signers_seeds_len: u64,
+        _memory_mapping: &mut MemoryMapping,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(0)
+    }
+);
+
+#[test]
+fn test_mock_cpi() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let system_account = AccountSharedData::from(Account {
+        lamports: 100_000_000,
+        owner: solana_sdk::system_program::id(),
+        ..Account::default()
+    });
+
+    let mut instruction_data = vec![5]; // TransferWithCpi
+    instruction_data.extend_from_slice(&42_000u64.to_le_bytes());
+
+    let context = test_context(
+        vec![
+            (sender, system_account.clone()),
+            (recipient, system_account),
+        ],
+        vec![
+            AccountMeta::new(sender, true),
+            AccountMeta::new(recipient, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+        ],
+        instruction_data,
+    );
+
+    // The mocked cross-program invocation succeeds without transferring.
+    let harness = Harness::default().with_mock_syscall(MockSyscall::new(
+        "sol_invoke_signed_rust",
+        SyscallInvokeSignedNoop::vm,
+    ));
+    let result = harness.execute_instruction(context, get_test_elf());
+    assert_eq!(result.result, Ok(()));
+    assert!(result.inner_instructions.is_empty());
+    for (key, account) in &result.resulting_accounts {
+        if *key == sender || *key == recipient {
+            assert_eq!(account.lamports(), 100_000_000);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use {
+    common::{process_fixture_differential, process_fixture_native, test_context},
+    protosol::fixture::{
+        context::FixtureContext, effects::FixtureEffects, sysvars::FixtureSysvarContext, Fixture,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData, ReadableAccount},
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, InstructionError},
+        program::invoke,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_instruction,
+    },
+};
+
+fn create_fixture(
+    accounts: Vec<(Pubkey, AccountSharedData)>,
+    instruction_accounts: Vec<AccountMeta>,
+    instruction_data: Vec<u8>,
+    sysvar_context: FixtureSysvarContext,
+    effects: FixtureEffects,
+) -> Fixture {
+    Fixture {
+        input: FixtureContext {
+            sysvar_context,
+            ..test_context(accounts, instruction_accounts, instruction_data)
+        },
+        output: effects,
+    }
+}
+
+fn program_account(lamports: u64, data: Vec<u8>) -> AccountSharedData {
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: test_program::id(),
+        ..Account::default()
+    })
+}
+
+#[test]
+fn test_native_write_data() {
+    let pubkey = Pubkey::new_unique();
+
+    process_fixture_native(create_fixture(
+        vec![(pubkey, program_account(100_000_000, vec![0; 4]))],
+        vec![AccountMeta::new(pubkey, false)],
+        vec![1, 4, 5, 6, 7], // WriteData
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, program_account(100_000_000, vec![4, 5, 6, 7]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    ));
+}
+
+#[test]
+fn test_native_write_clock_data() {
+    let pubkey = Pubkey::new_unique();
+    let clock_slot = 123u64;
+
+    let mut sysvar_context = FixtureSysvarContext::default();
+    sysvar_context.clock.slot = clock_slot;
+
+    process_fixture_native(create_fixture(
+        vec![(pubkey, program_account(100_000_000, vec![0; 8]))],
+        vec![AccountMeta::new(pubkey, false)],
+        vec![2], // WriteClockData
+        sysvar_context,
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(
+                pubkey,
+                program_account(100_000_000, clock_slot.to_le_bytes().to_vec()),
+            )],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    ));
+}
+
+#[test]
+fn test_native_close_account() {
+    let account = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    process_fixture_native(create_fixture(
+        vec![
+            (account, program_account(100_000_000, vec![1; 8])),
+            (destination, program_account(100_000_000, vec![])),
+        ],
+        vec![
+            AccountMeta::new(account, false),
+            AccountMeta::new(destination, false),
+        ],
+        vec![3], // CloseAccount
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![
+                (account, AccountSharedData::default()),
+                (destination, program_account(200_000_000, vec![])),
+            ],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    ));
+}
+
+#[test]
+fn test_native_transfer_sender_not_signer() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let mut instruction_data = vec![4]; // Transfer
+    instruction_data.extend_from_slice(&42_000u64.to_le_bytes());
+
+    process_fixture_native(create_fixture(
+        vec![
+            (sender, program_account(100_000_000, vec![])),
+            (recipient, program_account(100_000_000, vec![])),
+        ],
+        vec![
+            AccountMeta::new(sender, false), // Not a signer
+            AccountMeta::new(recipient, false),
+        ],
+        instruction_data,
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Err(InstructionError::MissingRequiredSignature),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    ));
+}
+
+#[test]
+fn test_native_transfer_with_cpi() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let transfer_amount = 42_000u64;
+
+    let system_account = |lamports| {
+        AccountSharedData::from(Account {
+            lamports,
+            owner: solana_sdk::system_program::id(),
+            ..Account::default()
+        })
+    };
+
+    let mut instruction_data = vec![5]; // TransferWithCpi
+    instruction_data.extend_from_slice(&transfer_amount.to_le_bytes());
+
+    process_fixture_native(create_fixture(
+        vec![
+            (sender, system_account(100_000_000)),
+            (recipient, system_account(100_000_000)),
+        ],
+        vec![
+            AccountMeta::new(sender, true),
+            AccountMeta::new(recipient, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+        ],
+        instruction_data,
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![
+                (sender, system_account(100_000_000 - transfer_amount)),
+                (recipient, system_account(100_000_000 + transfer_amount)),
+            ],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    ));
+}
+
+#[test]
+fn test_differential_write_data() {
+    let pubkey = Pubkey::new_unique();
+
+    process_fixture_differential(create_fixture(
+        vec![(pubkey, program_account(100_000_000, vec![0; 4]))],
+        vec![AccountMeta::new(pubkey, false)],
+        vec![1, 4, 5, 6, 7], // WriteData
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, program_account(100_000_000, vec![4, 5, 6, 7]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    ));
+}
+
+// Assigns the first account to the owner in the instruction data through the
+// system program, checking that the account info reflects the new owner.
+fn process_assign(_program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    let account = &accounts[0];
+    let owner = Pubkey::try_from(input).map_err(|_| ProgramError::InvalidInstructionData)?;
+    invoke(
+        &system_instruction::assign(account.key, &owner),
+        std::slice::from_ref(account),
+    )?;
+    if *account.owner != owner {
+        return Err(ProgramError::Custom(42));
+    }
+    Ok(())
+}
+
+// Writes the instruction data into the first account, if it's owned by the
+// program.
+fn process_write(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    let account = &accounts[0];
+    if account.owner != program_id {
+        return Err(ProgramError::Custom(42));
+    }
+    account.try_borrow_mut_data()?.copy_from_slice(input);
+    Ok(())
+}
+
+#[test]
+fn test_native_write_fail_incorrect_owner() {
+    let pubkey = Pubkey::new_unique();
+
+    let mut fixture = create_fixture(
+        vec![(pubkey, program_account(1_000_000, vec![0; 3]))],
+        vec![AccountMeta::new(pubkey, false)],
+        vec![1, 2, 3],
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, program_account(1_000_000, vec![1, 2, 3]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    );
+    let result = protosol::execute_instruction_native(fixture.input.clone(), process_write);
+    assert_eq!(result.result, Ok(()));
+    assert_eq!(result.program_load_error, None);
+    assert_eq!(result.resulting_accounts[0].0, pubkey);
+    assert_eq!(result.resulting_accounts[0].1.data(), &[1, 2, 3]);
+
+    fixture.input.accounts[0].1 = AccountSharedData::from(Account {
+        lamports: 1_000_000,
+        data: vec![0; 3],
+        owner: Pubkey::new_unique(),
+        ..Account::default()
+    });
+    let result = protosol::execute_instruction_native(fixture.input, process_write);
+    assert_eq!(result.result, Err(InstructionError::Custom(42)));
+}
+
+#[test]
+fn test_native_assign_with_cpi() {
+    let pubkey = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let account = |owner| {
+        AccountSharedData::from(Account {
+            lamports: 100_000_000,
+            owner,
+            ..Account::default()
+        })
+    };
+
+    let fixture = create_fixture(
+        vec![(pubkey, account(solana_sdk::system_program::id()))],
+        vec![
+            AccountMeta::new(pubkey, true),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+        ],
+        owner.to_bytes().to_vec(),
+        FixtureSysvarContext::default(),
+        FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(pubkey, account(owner))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    );
+
+    protosol::process_fixture_native(fixture, process_assign);
+}
@@ -3,12 +3,15 @@ mod common;
 use {
     common::process_fixture,
     protosol::fixture::{
-        context::FixtureContext, effects::FixtureEffects, sysvars::FixtureSysvarContext, Fixture,
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        sysvars::FixtureSysvarContext,
+        Fixture,
     },
     solana_sdk::{
         account::{Account, AccountSharedData},
         feature_set::FeatureSet,
-        instruction::AccountMeta,
+        instruction::{AccountMeta, InstructionError},
         pubkey::Pubkey,
     },
 };
@@ -53,11 +56,16 @@ fn test_noop() {
             instruction_data: vec![
                 0, // NoOp
             ],
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: 0,
-            custom_error: 0,
+            result: Ok(()),
             modified_accounts: vec![], // NoOp should not modify accounts.
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -86,11 +94,16 @@ fn test_noop_fail_bad_owners() {
                 instruction_data: vec![
                     0, // NoOp
                 ],
+                program_status: ProgramStatus::Deployed,
+                upgrade_authority: None,
+                deployment_slot: None,
             },
             output: FixtureEffects {
-                result: -1,                // -1 for failure
-                custom_error: 30064771072, // `ProgramError::IncorrectProgramId`
+                result: Err(InstructionError::IncorrectProgramId),
                 modified_accounts: vec![],
+                return_data: None,
+                inner_instructions: None,
+                modified_programs: None,
             },
         }
     };
@@ -0,0 +1,85 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::{
+        fixture::error::FixtureError,
+        harness::Harness,
+        privilege_scan::{run_privilege_scan, Privilege, PrivilegeVariant},
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+fn program_account() -> AccountSharedData {
+    AccountSharedData::from(Account {
+        lamports: 100_000_000,
+        owner: test_program::id(),
+        ..Account::default()
+    })
+}
+
+#[test]
+fn test_privilege_scan_missing_signer_check() {
+    let pubkey = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    // Closing an account doesn't check that the account signed.
+    let context = test_context(
+        vec![
+            (pubkey, program_account()),
+            (destination, AccountSharedData::default()),
+        ],
+        vec![
+            AccountMeta::new(pubkey, true),
+            AccountMeta::new(destination, false),
+        ],
+        vec![3], // CloseAccount
+    );
+
+    let report = run_privilege_scan(&Harness::default(), &context, get_test_elf()).unwrap();
+    assert_eq!(report.baseline.result, Ok(()));
+    assert_eq!(report.variants_executed, 3);
+    assert_eq!(report.escalations.len(), 1);
+    assert_eq!(
+        report.escalations[0].variant,
+        PrivilegeVariant {
+            instruction_account_index: 0,
+            pubkey,
+            privilege: Privilege::Signer,
+        }
+    );
+}
+
+#[test]
+fn test_privilege_scan_checked_transfer() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let mut instruction_data = vec![4]; // Transfer
+    instruction_data.extend_from_slice(&42_000u64.to_le_bytes());
+
+    let context = test_context(
+        vec![(sender, program_account()), (recipient, program_account())],
+        vec![
+            AccountMeta::new(sender, true),
+            AccountMeta::new(recipient, false),
+        ],
+        instruction_data,
+    );
+
+    let report = run_privilege_scan(&Harness::default(), &context, get_test_elf()).unwrap();
+    assert_eq!(report.variants_executed, 3);
+    assert!(report.escalations.is_empty());
+
+    // The fixture must pass.
+    let mut failing_context = context;
+    failing_context.instruction_accounts[0].is_signer = false;
+    assert_eq!(
+        run_privilege_scan(&Harness::default(), &failing_context, get_test_elf()).unwrap_err(),
+        FixtureError::BaselineFailed
+    );
+}
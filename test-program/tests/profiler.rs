@@ -0,0 +1,64 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::harness::Harness,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+    },
+};
+
+#[test]
+fn test_profile_transfer_with_cpi() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let system_account = AccountSharedData::from(Account {
+        lamports: 100_000_000,
+        owner: solana_sdk::system_program::id(),
+        ..Account::default()
+    });
+
+    let mut instruction_data = vec![5]; // TransferWithCpi
+    instruction_data.extend_from_slice(&42_000u64.to_le_bytes());
+
+    let context = test_context(
+        vec![
+            (sender, system_account.clone()),
+            (recipient, system_account),
+        ],
+        vec![
+            AccountMeta::new(sender, true),
+            AccountMeta::new(recipient, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+        ],
+        instruction_data,
+    );
+
+    let harness = Harness::default().with_profiling();
+    let result = harness.execute_instruction(context, get_test_elf());
+    assert_eq!(result.result, Ok(()));
+
+    let profile = harness.profile().unwrap();
+    let root = test_program::id().to_string();
+    assert!(profile.stacks.keys().all(|stack| stack.starts_with(&root)));
+
+    // Every compute unit is attributed.
+    assert_eq!(
+        profile.stacks.values().sum::<u64>(),
+        result.compute_units_consumed
+    );
+
+    // The System program invocation is a separate frame.
+    let cpi_frame = format!(";[CPI] {}", solana_sdk::system_program::id());
+    assert!(profile
+        .stacks
+        .keys()
+        .any(|stack| stack.contains(&cpi_frame)));
+
+    let mut folded = Vec::new();
+    profile.write_folded(&mut folded).unwrap();
+    assert!(!folded.is_empty());
+}
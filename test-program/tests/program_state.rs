@@ -0,0 +1,97 @@
+mod common;
+
+use {
+    common::{process_fixture, test_context},
+    protosol::fixture::{
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        sysvars::FixtureSysvarContext,
+        Fixture,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+};
+
+fn noop_context(
+    clock_slot: u64,
+    program_status: ProgramStatus,
+    deployment_slot: Option<u64>,
+) -> FixtureContext {
+    let pubkey = Pubkey::new_unique();
+
+    let mut sysvar_context = FixtureSysvarContext::default();
+    sysvar_context.clock.slot = clock_slot;
+
+    FixtureContext {
+        sysvar_context,
+        program_status,
+        upgrade_authority: Some(Pubkey::new_unique()),
+        deployment_slot,
+        ..test_context(
+            vec![(
+                pubkey,
+                AccountSharedData::from(Account {
+                    lamports: 100_000_000,
+                    owner: test_program::id(),
+                    ..Account::default()
+                }),
+            )],
+            vec![AccountMeta::new_readonly(pubkey, false)],
+            vec![
+                0, // NoOp
+            ],
+        )
+    }
+}
+
+#[test]
+fn test_program_deployed_in_previous_slot() {
+    let fixture = Fixture {
+        input: noop_context(100, ProgramStatus::Deployed, Some(99)),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    process_fixture(fixture);
+}
+
+#[test]
+fn test_program_fail_deployed_in_current_slot() {
+    // Programs deployed in the current slot are not visible until the next.
+    let fixture = Fixture {
+        input: noop_context(100, ProgramStatus::Deployed, Some(100)),
+        output: FixtureEffects {
+            result: Err(InstructionError::InvalidAccountData),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    process_fixture(fixture);
+}
+
+#[test]
+fn test_program_fail_closed() {
+    let fixture = Fixture {
+        input: noop_context(100, ProgramStatus::Closed, None),
+        output: FixtureEffects {
+            result: Err(InstructionError::InvalidAccountData),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    process_fixture(fixture);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use {
+    common::{process_fixture, process_fixture_native, test_context},
+    protosol::fixture::{effects::FixtureEffects, Fixture},
+    solana_sdk::transaction_context::TransactionReturnData,
+};
+
+fn create_fixture(data: &[u8], return_data: Option<TransactionReturnData>) -> Fixture {
+    let mut instruction_data = vec![6]; // SetReturnData
+    instruction_data.extend_from_slice(data);
+
+    Fixture {
+        input: test_context(vec![], vec![], instruction_data),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![],
+            return_data,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    }
+}
+
+#[test]
+fn test_set_return_data() {
+    process_fixture(create_fixture(
+        &[1, 2, 3],
+        Some(TransactionReturnData {
+            program_id: test_program::id(),
+            data: vec![1, 2, 3],
+        }),
+    ));
+}
+
+#[test]
+fn test_set_return_data_native() {
+    process_fixture_native(create_fixture(
+        &[1, 2, 3],
+        Some(TransactionReturnData {
+            program_id: test_program::id(),
+            data: vec![1, 2, 3],
+        }),
+    ));
+}
+
+#[test]
+fn test_set_empty_return_data_native() {
+    // Empty return data means no data was returned.
+    process_fixture_native(create_fixture(&[], Some(TransactionReturnData::default())));
+}
+
+#[test]
+#[should_panic(expected = "Return data mismatch")]
+fn test_set_return_data_mismatch_native() {
+    process_fixture_native(create_fixture(
+        &[1, 2, 3],
+        Some(TransactionReturnData {
+            program_id: test_program::id(),
+            data: vec![3, 2, 1],
+        }),
+    ));
+}
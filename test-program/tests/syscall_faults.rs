@@ -0,0 +1,79 @@
+mod common;
+
+use {
+    common::{get_test_elf, test_context},
+    protosol::{
+        fixture::context::FixtureContext,
+        harness::Harness,
+        syscall_faults::{SyscallFault, SyscallFaultEffect},
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+};
+
+fn create_transfer_context(amount: u64) -> FixtureContext {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let system_account = AccountSharedData::from(Account {
+        lamports: 100_000_000,
+        owner: solana_sdk::system_program::id(),
+        ..Account::default()
+    });
+
+    let mut instruction_data = vec![5]; // TransferWithCpi
+    instruction_data.extend_from_slice(&amount.to_le_bytes());
+
+    test_context(
+        vec![
+            (sender, system_account.clone()),
+            (recipient, system_account),
+        ],
+        vec![
+            AccountMeta::new(sender, true),
+            AccountMeta::new(recipient, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+        ],
+        instruction_data,
+    )
+}
+
+#[test]
+fn test_failed_cpi() {
+    let harness = Harness::default().with_syscall_fault(SyscallFault {
+        syscall: Some(String::from("sol_invoke_signed_rust")),
+        invocation: Some(0),
+        effect: SyscallFaultEffect::Error(InstructionError::Custom(3)),
+    });
+    let result = harness.execute_instruction(create_transfer_context(42_000), get_test_elf());
+    assert_eq!(result.result, Err(InstructionError::Custom(3)));
+    assert!(result.inner_instructions.is_empty());
+}
+
+#[test]
+fn test_exhausted_compute_meter() {
+    let harness = Harness::default().with_syscall_fault(SyscallFault {
+        syscall: None,
+        invocation: Some(0),
+        effect: SyscallFaultEffect::ExhaustComputeMeter,
+    });
+    let result = harness.execute_instruction(create_transfer_context(42_000), get_test_elf());
+    assert_eq!(
+        result.result,
+        Err(InstructionError::ComputationalBudgetExceeded)
+    );
+}
+
+#[test]
+fn test_unmatched_fault() {
+    let harness = Harness::default().with_syscall_fault(SyscallFault {
+        syscall: Some(String::from("sol_invoke_signed_rust")),
+        invocation: Some(1),
+        effect: SyscallFaultEffect::Error(InstructionError::Custom(3)),
+    });
+    let result = harness.execute_instruction(create_transfer_context(42_000), get_test_elf());
+    assert_eq!(result.result, Ok(()));
+}
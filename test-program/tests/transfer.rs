@@ -3,12 +3,15 @@ mod common;
 use {
     common::process_fixture,
     protosol::fixture::{
-        context::FixtureContext, effects::FixtureEffects, sysvars::FixtureSysvarContext, Fixture,
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        sysvars::FixtureSysvarContext,
+        Fixture,
     },
     solana_sdk::{
         account::{Account, AccountSharedData},
         feature_set::FeatureSet,
-        instruction::AccountMeta,
+        instruction::{AccountMeta, InstructionError},
         pubkey::Pubkey,
     },
     std::vec,
@@ -77,11 +80,16 @@ fn test_transfer() {
             accounts: account_inputs,
             instruction_accounts,
             instruction_data,
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: 0,
-            custom_error: 0,
+            result: Ok(()),
             modified_accounts,
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -114,11 +122,16 @@ fn test_transfer_bad_owners() {
                 accounts: account_inputs,
                 instruction_accounts,
                 instruction_data,
+                program_status: ProgramStatus::Deployed,
+                upgrade_authority: None,
+                deployment_slot: None,
             },
             output: FixtureEffects {
-                result: -1,                // -1 for failure
-                custom_error: 30064771072, // `ProgramError::IncorrectProgramId`
+                result: Err(InstructionError::IncorrectProgramId),
                 modified_accounts: vec![],
+                return_data: None,
+                inner_instructions: None,
+                modified_programs: None,
             },
         }
     };
@@ -208,11 +221,16 @@ fn test_transfer_sender_not_signer() {
             accounts: account_inputs,
             instruction_accounts,
             instruction_data,
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: -1,                // -1 for failure
-            custom_error: 34359738368, // `ProgramError::MissingRequiredSignature`
+            result: Err(InstructionError::MissingRequiredSignature),
             modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -263,11 +281,16 @@ fn test_transfer_sender_not_enough_lamports() {
             accounts: account_inputs,
             instruction_accounts,
             instruction_data,
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: -1,                // -1 for failure
-            custom_error: 25769803776, // `ProgramError::34359738368`
+            result: Err(InstructionError::InsufficientFunds),
             modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -0,0 +1,447 @@
+use {
+    protosol::{
+        fixture::{
+            context::{FixtureContext, ProgramStatus},
+            effects::FixtureEffects,
+            sysvars::FixtureSysvarContext,
+            Fixture,
+        },
+        result::{ModifiedProgram, ModifiedProgramStatus},
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData, ReadableAccount, WritableAccount},
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        feature_set::FeatureSet,
+        instruction::{Instruction, InstructionError},
+        pubkey::Pubkey,
+        rent::Rent,
+    },
+};
+
+// Builtin programs need no ELF.
+const NO_ELF: &[u8] = &[];
+
+// A program which returns successfully, built for the upgradeable loader.
+fn noop_elf() -> &'static [u8] {
+    include_bytes!("elfs/noop_aligned.so")
+}
+
+fn context(instruction: Instruction, accounts: Vec<(Pubkey, AccountSharedData)>) -> FixtureContext {
+    FixtureContext {
+        program_id: instruction.program_id,
+        loader_id: solana_sdk::native_loader::id(),
+        feature_set: FeatureSet::all_enabled(),
+        sysvar_context: FixtureSysvarContext::default(),
+        accounts,
+        instruction_accounts: instruction.accounts,
+        instruction_data: instruction.data,
+        program_status: ProgramStatus::Deployed,
+        upgrade_authority: None,
+        deployment_slot: None,
+    }
+}
+
+fn loader_account(state: &UpgradeableLoaderState, data_len: usize) -> AccountSharedData {
+    let mut data = bincode::serialize(state).unwrap();
+    data.resize(data_len, 0);
+    AccountSharedData::from(Account {
+        lamports: Rent::default().minimum_balance(data_len),
+        data,
+        owner: bpf_loader_upgradeable::id(),
+        ..Account::default()
+    })
+}
+
+fn buffer_account(authority: &Pubkey, bytes: &[u8]) -> AccountSharedData {
+    let mut account = loader_account(
+        &UpgradeableLoaderState::Buffer {
+            authority_address: Some(*authority),
+        },
+        UpgradeableLoaderState::size_of_buffer(bytes.len()),
+    );
+    account.data_as_mut_slice()[UpgradeableLoaderState::size_of_buffer_metadata()..]
+        .copy_from_slice(bytes);
+    account
+}
+
+fn system_account(lamports: u64) -> AccountSharedData {
+    AccountSharedData::from(Account {
+        lamports,
+        owner: solana_sdk::system_program::id(),
+        ..Account::default()
+    })
+}
+
+#[test]
+fn test_initialize_buffer() {
+    let payer = Pubkey::new_unique();
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+
+    let uninitialized = loader_account(
+        &UpgradeableLoaderState::Uninitialized,
+        UpgradeableLoaderState::size_of_buffer(4),
+    );
+
+    // Skip the system program's `CreateAccount`.
+    let instruction = bpf_loader_upgradeable::create_buffer(&payer, &buffer, &authority, 0, 4)
+        .unwrap()
+        .remove(1);
+
+    let fixture = Fixture {
+        input: context(
+            instruction,
+            vec![(buffer, uninitialized), (authority, system_account(0))],
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(buffer, buffer_account(&authority, &[0; 4]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+fn test_write() {
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+
+    let instruction = bpf_loader_upgradeable::write(&buffer, &authority, 1, vec![7, 7]);
+
+    let fixture = Fixture {
+        input: context(
+            instruction,
+            vec![
+                (buffer, buffer_account(&authority, &[0; 4])),
+                (authority, system_account(0)),
+            ],
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(buffer, buffer_account(&authority, &[0, 7, 7, 0]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+fn test_write_fail_incorrect_authority() {
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let other_authority = Pubkey::new_unique();
+
+    let instruction = bpf_loader_upgradeable::write(&buffer, &other_authority, 0, vec![7]);
+
+    let fixture = Fixture {
+        input: context(
+            instruction,
+            vec![
+                (buffer, buffer_account(&authority, &[0; 4])),
+                (other_authority, system_account(0)),
+            ],
+        ),
+        output: FixtureEffects {
+            result: Err(InstructionError::IncorrectAuthority),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+fn test_set_buffer_authority() {
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let new_authority = Pubkey::new_unique();
+
+    let instruction =
+        bpf_loader_upgradeable::set_buffer_authority(&buffer, &authority, &new_authority);
+
+    let fixture = Fixture {
+        input: context(
+            instruction,
+            vec![
+                (buffer, buffer_account(&authority, &[1, 2, 3, 4])),
+                (authority, system_account(0)),
+                (new_authority, system_account(0)),
+            ],
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![(buffer, buffer_account(&new_authority, &[1, 2, 3, 4]))],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+fn test_close_buffer() {
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let buffer_account = buffer_account(&authority, &[1, 2, 3, 4]);
+    let buffer_lamports = buffer_account.lamports();
+
+    let instruction = bpf_loader_upgradeable::close(&buffer, &recipient, &authority);
+
+    let mut closed_buffer_account = loader_account(
+        &UpgradeableLoaderState::Uninitialized,
+        UpgradeableLoaderState::size_of_uninitialized(),
+    );
+    closed_buffer_account.set_lamports(0);
+
+    let fixture = Fixture {
+        input: context(
+            instruction,
+            vec![
+                (buffer, buffer_account),
+                (recipient, system_account(0)),
+                (authority, system_account(0)),
+            ],
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![
+                (buffer, closed_buffer_account),
+                (recipient, system_account(buffer_lamports)),
+            ],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
+        },
+    };
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+fn test_deploy_with_max_data_len() {
+    let payer = Pubkey::new_unique();
+    let program = Pubkey::new_unique();
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let elf = noop_elf();
+
+    let programdata_address =
+        Pubkey::find_program_address(&[program.as_ref()], &bpf_loader_upgradeable::id()).0;
+
+    // Skip the system program's `CreateAccount`.
+    let instruction = bpf_loader_upgradeable::deploy_with_max_program_len(
+        &payer,
+        &program,
+        &buffer,
+        &authority,
+        0,
+        elf.len(),
+    )
+    .unwrap()
+    .remove(1);
+
+    let context = context(
+        instruction,
+        vec![
+            (payer, system_account(10_000_000_000)),
+            (programdata_address, AccountSharedData::default()),
+            (
+                program,
+                loader_account(
+                    &UpgradeableLoaderState::Uninitialized,
+                    UpgradeableLoaderState::size_of_program(),
+                ),
+            ),
+            (buffer, buffer_account(&authority, elf)),
+            (solana_sdk::sysvar::rent::id(), AccountSharedData::default()),
+            (
+                solana_sdk::sysvar::clock::id(),
+                AccountSharedData::default(),
+            ),
+            (authority, system_account(0)),
+        ],
+    );
+
+    let result = protosol::execute_instruction(context, NO_ELF);
+    assert_eq!(result.result, Ok(()));
+    assert_eq!(
+        result.modified_programs,
+        vec![ModifiedProgram {
+            program_id: program,
+            deployment_slot: 0,
+            status: ModifiedProgramStatus::Deployed,
+        }]
+    );
+}
+
+// A program deployed in slot 0, and its program data address and account.
+fn deployed_program(
+    program: &Pubkey,
+    authority: &Pubkey,
+    elf: &[u8],
+) -> (AccountSharedData, Pubkey, AccountSharedData) {
+    let programdata_address =
+        Pubkey::find_program_address(&[program.as_ref()], &bpf_loader_upgradeable::id()).0;
+
+    let mut program_account = loader_account(
+        &UpgradeableLoaderState::Program {
+            programdata_address,
+        },
+        UpgradeableLoaderState::size_of_program(),
+    );
+    program_account.set_executable(true);
+
+    let mut programdata_account = loader_account(
+        &UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(*authority),
+        },
+        UpgradeableLoaderState::size_of_programdata(elf.len()),
+    );
+    programdata_account.data_as_mut_slice()
+        [UpgradeableLoaderState::size_of_programdata_metadata()..]
+        .copy_from_slice(elf);
+
+    (program_account, programdata_address, programdata_account)
+}
+
+// Programs can't be upgraded or closed in the slot they were deployed in.
+fn later_slot_context(
+    instruction: Instruction,
+    accounts: Vec<(Pubkey, AccountSharedData)>,
+) -> FixtureContext {
+    let mut context = context(instruction, accounts);
+    context.sysvar_context.clock.slot = 10;
+    context
+}
+
+fn upgrade_fixture(modified_programs: Vec<ModifiedProgram>) -> Fixture {
+    let program = Pubkey::new_unique();
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let spill = Pubkey::new_unique();
+    let elf = noop_elf();
+
+    let (program_account, programdata_address, programdata_account) =
+        deployed_program(&program, &authority, elf);
+
+    let instruction = bpf_loader_upgradeable::upgrade(&program, &buffer, &authority, &spill);
+
+    Fixture {
+        input: later_slot_context(
+            instruction,
+            vec![
+                (programdata_address, programdata_account),
+                (program, program_account),
+                (buffer, buffer_account(&authority, elf)),
+                (spill, system_account(0)),
+                (solana_sdk::sysvar::rent::id(), AccountSharedData::default()),
+                (
+                    solana_sdk::sysvar::clock::id(),
+                    AccountSharedData::default(),
+                ),
+                (authority, system_account(0)),
+            ],
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: Some(
+                modified_programs
+                    .into_iter()
+                    .map(|modified_program| ModifiedProgram {
+                        program_id: program,
+                        ..modified_program
+                    })
+                    .collect(),
+            ),
+        },
+    }
+}
+
+#[test]
+fn test_upgrade() {
+    let fixture = upgrade_fixture(vec![ModifiedProgram {
+        program_id: Pubkey::default(),
+        deployment_slot: 10,
+        status: ModifiedProgramStatus::Deployed,
+    }]);
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+#[should_panic(expected = "Modified programs mismatch")]
+fn test_upgrade_fail_modified_programs_mismatch() {
+    let fixture = upgrade_fixture(vec![]);
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
+
+#[test]
+fn test_close_program() {
+    let program = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let (program_account, programdata_address, programdata_account) =
+        deployed_program(&program, &authority, noop_elf());
+    let programdata_lamports = programdata_account.lamports();
+
+    let instruction = bpf_loader_upgradeable::close_any(
+        &programdata_address,
+        &recipient,
+        Some(&authority),
+        Some(&program),
+    );
+
+    let mut closed_programdata_account = loader_account(
+        &UpgradeableLoaderState::Uninitialized,
+        UpgradeableLoaderState::size_of_uninitialized(),
+    );
+    closed_programdata_account.set_lamports(0);
+
+    let fixture = Fixture {
+        input: later_slot_context(
+            instruction,
+            vec![
+                (programdata_address, programdata_account),
+                (recipient, system_account(0)),
+                (authority, system_account(0)),
+                (program, program_account),
+            ],
+        ),
+        output: FixtureEffects {
+            result: Ok(()),
+            modified_accounts: vec![
+                (programdata_address, closed_programdata_account),
+                (recipient, system_account(programdata_lamports)),
+            ],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: Some(vec![ModifiedProgram {
+                program_id: program,
+                deployment_slot: 10,
+                status: ModifiedProgramStatus::Closed,
+            }]),
+        },
+    };
+
+    protosol::process_fixture(fixture, NO_ELF);
+}
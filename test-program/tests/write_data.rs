@@ -3,12 +3,15 @@ mod common;
 use {
     common::process_fixture,
     protosol::fixture::{
-        context::FixtureContext, effects::FixtureEffects, sysvars::FixtureSysvarContext, Fixture,
+        context::{FixtureContext, ProgramStatus},
+        effects::FixtureEffects,
+        sysvars::FixtureSysvarContext,
+        Fixture,
     },
     solana_sdk::{
         account::{Account, AccountSharedData},
         feature_set::FeatureSet,
-        instruction::AccountMeta,
+        instruction::{AccountMeta, InstructionError},
         pubkey::Pubkey,
     },
 };
@@ -53,11 +56,16 @@ fn test_write_data() {
             accounts: account_inputs,
             instruction_accounts,
             instruction_data,
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: 0,
-            custom_error: 0,
+            result: Ok(()),
             modified_accounts,
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -94,11 +102,16 @@ fn test_write_data_fail_bad_owner() {
             accounts: account_inputs,
             instruction_accounts,
             instruction_data,
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: -1,                // -1 for failure
-            custom_error: 30064771072, // `ProgramError::IncorrectProgramId`
+            result: Err(InstructionError::IncorrectProgramId),
             modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 
@@ -136,11 +149,16 @@ fn test_write_data_fail_bad_account_data() {
             accounts: account_inputs,
             instruction_accounts,
             instruction_data,
+            program_status: ProgramStatus::Deployed,
+            upgrade_authority: None,
+            deployment_slot: None,
         },
         output: FixtureEffects {
-            result: -1,                // -1 for failure
-            custom_error: 17179869184, // `ProgramError::InvalidAccountData`
+            result: Err(InstructionError::InvalidAccountData),
             modified_accounts: vec![],
+            return_data: None,
+            inner_instructions: None,
+            modified_programs: None,
         },
     };
 